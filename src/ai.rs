@@ -1,4 +1,7 @@
+use std::time::{Duration, Instant};
+
 use fnv::FnvBuildHasher;
+use rand::{seq::IteratorRandom as _, Rng};
 use rayon::iter::ParallelIterator as _;
 use scc::{hash_map::Entry, HashMap};
 
@@ -33,33 +36,46 @@ fn densely_pack(board: [Option<Player>; (N*N) as usize], p: Player) -> u64 {
     m
 }
 
-pub fn maximize(st: State, p: Player) -> Option<(u8, u8)> {
-    fn inner(st: State, p: Player, par_depth: u8, mut alpha: i8, beta: i8) -> (i8, Option<(u8, u8)>) {
+/// Finds the best move for `p` along with the outcome it leads to with best
+/// play: 1 if `p` wins, -1 if it loses and 0 for a draw.
+pub fn maximize(st: State, p: Player) -> (i8, Option<(u8, u8)>) {
+    maximize_until(st, p, || false).unwrap()
+}
+
+/// Like [`maximize`], but gives up with `None` once `stop` holds, which it
+/// checks at every position. What was proven by then stays memoized, so
+/// searching again picks up about where this left off.
+pub fn maximize_until(st: State, p: Player, stop: impl Fn() -> bool + Sync) -> Option<(i8, Option<(u8, u8)>)> {
+    fn inner(st: State, p: Player, par_depth: u8, mut alpha: i8, beta: i8, stop: &(impl Fn() -> bool + Sync)) -> Option<(i8, Option<(u8, u8)>)> {
         // st caches wins, so this is faster than memo
         if let Some(score) = st.score() {
-            return match score {
+            return Some(match score {
                 Score::Win(w) if w == p => (1, None),
                 Score::Win(_) => (-1, None),
                 Score::Tie => (0, None)
-            };
+            });
         }
 
         let m = densely_pack(st.board(), p);
 
         if let Some((score, pos, bound)) = MEMO.read_sync(&m, |_, &v| v) &&
             (bound == Bound::Exact || (bound == Bound::Lower && score >= beta) || (bound == Bound::Upper && score <= alpha)) {
-            return (score, pos)
+            return Some((score, pos))
         }
 
         assert_eq!(st.turn(), Some(p));
 
+        if stop() {
+            return None
+        }
+
         if par_depth == 0 {
             let old_alpha = alpha;
 
             let mut max = None;
             for (x, y) in st.succs() {
                 let nst = st.do_move(x, y).unwrap();
-                let (score, _) = inner(nst, p.other(), 0, -beta, -alpha);
+                let (score, _) = inner(nst, p.other(), 0, -beta, -alpha, stop)?;
                 let score = -score;
                 if max.is_none_or(|(ms, _)| score > ms) {
                     max = Some((score, Some((x, y))));
@@ -81,21 +97,194 @@ pub fn maximize(st: State, p: Player) -> Option<(u8, u8)> {
                 };
                 ent.insert_entry((score, pos, bound));
             }
-            (score, pos)
+            Some((score, pos))
         } else {
             let (score, pos) = st.par_succs()
                 .map(|(x, y)| {
                     let nst = st.do_move(x, y).unwrap();
-                    let (score, _) = inner(nst, p.other(), par_depth - 1, -beta, -alpha);
-                    (-score, Some((x, y)))
+                    let (score, _) = inner(nst, p.other(), par_depth - 1, -beta, -alpha, stop)?;
+                    Some((-score, Some((x, y))))
                 })
+                .collect::<Option<Vec<_>>>()?
+                .into_iter()
                 .max_by_key(|&(score, _)| score).unwrap();
             
             MEMO.upsert_sync(m, (score, pos, Bound::Exact));
 
-            (score, pos)
+            Some((score, pos))
+        }
+    }
+
+    inner(st, p, 2, -2, 2, &stop)
+}
+
+/// How far [`deepen`] may go. No limits at all means searching until the
+/// outcome is proven.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    pub depth: Option<u8>,
+    pub time: Option<Duration>,
+    pub eval: Eval
+}
+
+/// How [`deepen`] scores the positions it can't see the end of.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Eval {
+    /// All as draws.
+    #[default]
+    Blind,
+    /// By how many more lines the side to move could still complete than
+    /// its opponent.
+    Lines
+}
+
+/// What a won position scores inside [`deepen`], above any evaluation.
+const WIN: i8 = 100;
+
+impl Eval {
+    fn score(self, st: State, p: Player) -> i8 {
+        match self {
+            Eval::Blind => 0,
+            Eval::Lines => (st.open_lines(p) as i8 - st.open_lines(p.other()) as i8).clamp(1 - WIN, WIN - 1)
+        }
+    }
+}
+
+/// What a search to `depth` plies found: the outcome if proven, and the line
+/// of play it expects, best move first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Line {
+    pub depth: u8,
+    pub score: Option<i8>,
+    pub pv: Vec<(u8, u8)>
+}
+
+/// Like [`maximize`], but searches one ply deeper at a time until the
+/// outcome is proven or `limits` are reached, and settles for the best line
+/// the last full iteration found. Each iteration's line goes to `report`.
+pub fn deepen(st: State, p: Player, limits: Limits, mut report: impl FnMut(&Line)) -> Line {
+    /// Scores positions it can't see the end of within `depth` plies by
+    /// `eval`, and also returns whether it saw the end everywhere. Gives up
+    /// with `None` at the deadline.
+    fn inner(st: State, p: Player, depth: u8, mut alpha: i8, beta: i8, eval: Eval, deadline: Option<Instant>) -> Option<(i8, Vec<(u8, u8)>, bool)> {
+        if let Some(score) = st.score() {
+            return Some(match score {
+                Score::Win(w) if w == p => (WIN, Vec::new(), true),
+                Score::Win(_) => (-WIN, Vec::new(), true),
+                Score::Tie => (0, Vec::new(), true)
+            });
+        }
+        if depth == 0 {
+            return Some((eval.score(st, p), Vec::new(), false))
+        }
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            return None
+        }
+
+        let mut max = None;
+        let mut complete = true;
+        for (x, y) in st.succs() {
+            let nst = st.do_move(x, y).unwrap();
+            let (score, pv, seen) = inner(nst, p.other(), depth - 1, -beta, -alpha, eval, deadline)?;
+            let score = -score;
+            complete &= seen;
+            if max.as_ref().is_none_or(|&(ms, _)| score > ms) {
+                max = Some((score, [(x, y)].into_iter().chain(pv).collect()));
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta { break }
+        }
+
+        let (score, pv) = max.unwrap();
+        Some((score, pv, complete))
+    }
+
+    let deadline = limits.time.map(|t| Instant::now() + t);
+    // Something to play even if not a single ply can be searched in time.
+    let mut found = Line { depth: 0, score: None, pv: st.succs().take(1).collect() };
+    for depth in 1..=limits.depth.unwrap_or(u8::MAX).min((N*N) as u8) {
+        let Some((score, pv, complete)) = inner(st, p, depth, -WIN - 1, WIN + 1, limits.eval, deadline) else { break };
+        // A win or a loss can't be overturned by unseen positions, which
+        // always score short of one.
+        let proven = complete || score.abs() == WIN;
+        found = Line { depth, score: proven.then_some(score.signum()), pv };
+        report(&found);
+        if proven { break }
+    }
+    found
+}
+
+/// [`deepen`] with only a time limit, for the best move and the outcome if
+/// proven.
+pub fn search(st: State, p: Player, budget: Duration) -> (Option<i8>, Option<(u8, u8)>) {
+    let line = deepen(st, p, Limits { depth: None, time: Some(budget), ..Limits::default() }, |_| ());
+    (line.score, line.pv.first().copied())
+}
+
+/// Picks a move for `p` by Monte Carlo tree search: `iterations` random
+/// playouts, each from the most promising unexplored position by UCT.
+pub fn mcts(st: State, p: Player, iterations: u32, rng: &mut impl Rng) -> Option<(u8, u8)> {
+    struct Node {
+        /// The move that led here and who made it.
+        mv: Option<((u8, u8), Player)>,
+        st: State,
+        children: Vec<usize>,
+        untried: Vec<(u8, u8)>,
+        visits: u32,
+        /// Sum of the playouts' rewards for whoever made `mv`.
+        reward: f32
+    }
+
+    fn node(st: State, mv: Option<((u8, u8), Player)>) -> Node {
+        let untried = if st.score().is_none() { st.succs().collect() } else { Vec::new() };
+        Node { mv, st, children: Vec::new(), untried, visits: 0, reward: 0. }
+    }
+
+    assert_eq!(st.turn(), Some(p));
+    let mut tree = vec![node(st, None)];
+    for _ in 0..iterations {
+        let mut path = vec![0];
+        let mut i = 0;
+        while tree[i].untried.is_empty() && !tree[i].children.is_empty() {
+            let ln = (tree[i].visits as f32).ln();
+            i = *tree[i].children.iter().max_by(|&&a, &&b| {
+                let uct = |n: &Node| n.reward / n.visits as f32 + (2. * ln / n.visits as f32).sqrt();
+                uct(&tree[a]).total_cmp(&uct(&tree[b]))
+            }).unwrap();
+            path.push(i);
+        }
+
+        if !tree[i].untried.is_empty() {
+            let pick = rng.random_range(0..tree[i].untried.len());
+            let (x, y) = tree[i].untried.swap_remove(pick);
+            let mover = tree[i].st.turn().unwrap();
+            tree.push(node(tree[i].st.do_move(x, y).unwrap(), Some(((x, y), mover))));
+            let child = tree.len() - 1;
+            tree[i].children.push(child);
+            i = child;
+            path.push(i);
+        }
+
+        let mut end = tree[i].st;
+        let score = loop {
+            if let Some(score) = end.score() { break score }
+            let (x, y) = end.succs().choose(rng).unwrap();
+            end = end.do_move(x, y).unwrap();
+        };
+
+        for &i in &path {
+            let n = &mut tree[i];
+            n.visits += 1;
+            n.reward += match (score, n.mv) {
+                (Score::Tie, _) => 0.5,
+                (Score::Win(w), Some((_, mover))) if w == mover => 1.,
+                _ => 0.
+            };
         }
     }
 
-    inner(st, p, 2, -2, 2).1
-}
\ No newline at end of file
+    tree[0].children.iter()
+        .max_by_key(|&&c| tree[c].visits)
+        .and_then(|&c| tree[c].mv)
+        .map(|(mv, _)| mv)
+}
@@ -0,0 +1,120 @@
+use std::io;
+
+use rand::{rngs::StdRng, seq::IteratorRandom as _, Rng as _, SeedableRng as _};
+use rayon::prelude::*;
+use ttt::{
+    game::{Player, Record, Score},
+    N
+};
+
+use crate::{contestant::Contestant, stats::Results};
+
+/// The most random plies an opening can have, so that nobody can have won
+/// by the end of it.
+pub const MAX_OPENING: u32 = 2 * N - 2;
+
+/// A match between `a` and `b`. Games come in pairs from the same random
+/// opening, with `a` playing X in the first and O in the second.
+#[derive(Debug, Clone)]
+pub struct Arena {
+    pub a: Contestant,
+    pub b: Contestant,
+    pub games: u32,
+    pub seed: u64,
+    /// How many random plies each pair of games starts with.
+    pub openings: u32
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Game {
+    pub a_plays: Player,
+    pub record: Record,
+    pub score: Score
+}
+
+impl Arena {
+    /// Plays all the games in parallel. Each pair of games draws on its own
+    /// generator seeded from `seed`, so the order they're played in doesn't
+    /// change a thing.
+    pub fn run(&self) -> io::Result<Vec<Game>> {
+        assert!(self.openings <= MAX_OPENING);
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let seeds = (0..self.games.div_ceil(2)).map(|_| rng.random()).collect::<Vec<u64>>();
+        (0..self.games).into_par_iter()
+            .map(|i| self.play(seeds[i as usize / 2], if i % 2 == 0 { Player::X } else { Player::O }))
+            .collect()
+    }
+
+    fn play(&self, seed: u64, a_plays: Player) -> io::Result<Game> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut record = Record::default();
+        let mut st = record.state_at(0);
+        for _ in 0..self.openings {
+            let (x, y) = st.succs().choose(&mut rng).unwrap();
+            record.push(x, y);
+            st = st.do_move(x, y).unwrap();
+        }
+
+        let mut seats = [self.a.seat()?, self.b.seat()?];
+        if a_plays == Player::O {
+            seats.swap(0, 1);
+        }
+        while let Some(p) = st.turn() {
+            let (x, y) = seats[p as usize].choose(&record, &mut rng)?;
+            record.push(x, y);
+            st = st.do_move(x, y).unwrap();
+        }
+        Ok(Game { a_plays, record, score: st.score().unwrap() })
+    }
+}
+
+pub fn results(games: &[Game]) -> Results {
+    let mut results = Results::default();
+    for game in games {
+        match game.score {
+            Score::Win(w) if w == game.a_plays => results.wins += 1,
+            Score::Win(_) => results.losses += 1,
+            Score::Tie => results.draws += 1
+        }
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arena(seed: u64) -> Arena {
+        Arena { a: Contestant::Mcts(200), b: Contestant::Random, games: 10, seed, openings: 2 }
+    }
+
+    #[test]
+    fn the_seed_decides_everything() {
+        let games = arena(7).run().unwrap();
+        assert_eq!(arena(7).run().unwrap(), games);
+        assert_ne!(arena(8).run().unwrap(), games);
+    }
+
+    #[test]
+    fn pairs_swap_sides_from_the_same_opening() {
+        let games = arena(1).run().unwrap();
+        for pair in games.chunks(2) {
+            assert_eq!([pair[0].a_plays, pair[1].a_plays], [Player::X, Player::O]);
+            assert_eq!(pair[0].record.moves()[..2], pair[1].record.moves()[..2]);
+        }
+    }
+
+    #[test]
+    fn search_beats_random_play() {
+        let results = results(&arena(0).run().unwrap());
+        assert_eq!(results.games(), 10);
+        assert!(results.score() > 0.5, "{results}");
+    }
+
+    #[test]
+    fn counting_lines_beats_a_blind_search() {
+        let arena = Arena { a: "depth:3:lines".parse().unwrap(), b: "depth:3".parse().unwrap(), games: 40, seed: 0, openings: 2 };
+        let results = results(&arena.run().unwrap());
+        assert!(results.score() > 0.5, "{results}");
+    }
+}
@@ -0,0 +1,145 @@
+use std::{error::Error, fmt::Display, io, str::FromStr};
+
+use rand::{rngs::StdRng, seq::IteratorRandom as _};
+use ttt::{
+    ai::{deepen, maximize, mcts, Eval, Limits},
+    engine::{External, Go},
+    game::{Player, Record, Score, State}
+};
+
+/// One side of the match, as given on the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Contestant {
+    /// Plays perfectly, choosing at random between equally good moves.
+    Minimax,
+    /// Searches this many plies ahead, scoring what it can't see the end of
+    /// by `Eval`: as a draw unless given as `depth:N:lines`.
+    Depth(u8, Eval),
+    /// Monte Carlo tree search with this many playouts a move.
+    Mcts(u32),
+    Random,
+    /// An engine process, started from this command line, thinking without
+    /// limits. Only as reproducible as the engine.
+    Engine(String)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseContestantError;
+
+impl Display for ParseContestantError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("invalid contestant, expected `minimax`, `depth:N[:lines]`, `mcts:N`, `random` or `engine:CMD`")
+    }
+}
+
+impl Error for ParseContestantError {}
+
+impl FromStr for Contestant {
+    type Err = ParseContestantError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, arg) = s.split_once(':').unwrap_or((s, ""));
+        match (kind, arg) {
+            ("minimax", "") => Ok(Contestant::Minimax),
+            ("random", "") => Ok(Contestant::Random),
+            ("depth", arg) => {
+                let (n, eval) = match arg.split_once(':') {
+                    Some((n, "lines")) => (n, Eval::Lines),
+                    Some(_) => return Err(ParseContestantError),
+                    None => (arg, Eval::Blind)
+                };
+                n.parse().ok().filter(|&n| n > 0).map(|n| Contestant::Depth(n, eval)).ok_or(ParseContestantError)
+            },
+            ("mcts", n) => n.parse().ok().filter(|&n| n > 0).map(Contestant::Mcts).ok_or(ParseContestantError),
+            ("engine", cmd) if !cmd.trim().is_empty() => Ok(Contestant::Engine(cmd.to_owned())),
+            _ => Err(ParseContestantError)
+        }
+    }
+}
+
+impl Display for Contestant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Contestant::Minimax => f.write_str("minimax"),
+            Contestant::Depth(n, Eval::Blind) => write!(f, "depth:{n}"),
+            Contestant::Depth(n, Eval::Lines) => write!(f, "depth:{n}:lines"),
+            Contestant::Mcts(n) => write!(f, "mcts:{n}"),
+            Contestant::Random => f.write_str("random"),
+            Contestant::Engine(cmd) => write!(f, "engine:{cmd}")
+        }
+    }
+}
+
+/// A contestant set up for one game.
+pub enum Seat {
+    Minimax,
+    Depth(u8, Eval),
+    Mcts(u32),
+    Random,
+    Engine(External)
+}
+
+impl Contestant {
+    /// Starts the engine process if there is one.
+    pub fn seat(&self) -> io::Result<Seat> {
+        Ok(match self {
+            Contestant::Minimax => Seat::Minimax,
+            Contestant::Depth(depth, eval) => Seat::Depth(*depth, *eval),
+            Contestant::Mcts(iterations) => Seat::Mcts(*iterations),
+            Contestant::Random => Seat::Random,
+            Contestant::Engine(cmd) => {
+                let mut words = cmd.split_whitespace().map(str::to_owned);
+                let program = words.next().unwrap();
+                Seat::Engine(External::spawn(&program, &words.collect::<Vec<_>>())?)
+            }
+        })
+    }
+}
+
+impl Seat {
+    /// The move to play after `record`, whose game isn't over.
+    pub fn choose(&mut self, record: &Record, rng: &mut StdRng) -> io::Result<(u8, u8)> {
+        let st = record.state_at(record.len());
+        let p = st.turn().unwrap();
+        let mv = match self {
+            Seat::Minimax => {
+                let (best, _) = maximize(st, p);
+                st.succs().filter(|&mv| outcome_of(st, p, mv) == best).choose(rng)
+            },
+            Seat::Depth(depth, eval) => deepen(st, p, Limits { depth: Some(*depth), time: None, eval: *eval }, |_| ()).pv.first().copied(),
+            Seat::Mcts(iterations) => mcts(st, p, *iterations, rng),
+            Seat::Random => st.succs().choose(rng),
+            Seat::Engine(engine) => engine.go(record, Go::default())?.0
+        };
+
+        mv.filter(|&(x, y)| st.do_move(x, y).is_ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no legal move played"))
+    }
+}
+
+/// The outcome for `p` of playing `mv`, with best play after: 1 if `p` wins,
+/// -1 if it loses and 0 for a draw.
+fn outcome_of(st: State, p: Player, (x, y): (u8, u8)) -> i8 {
+    let nst = st.do_move(x, y).unwrap();
+    match (nst.turn(), nst.score()) {
+        (Some(q), _) => -maximize(nst, q).0,
+        (None, Some(Score::Win(w))) if w == p => 1,
+        (None, Some(Score::Win(_))) => -1,
+        (None, _) => 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_each_kind() {
+        for s in ["minimax", "depth:3", "depth:2:lines", "mcts:500", "random", "engine:ttt engine"] {
+            assert_eq!(s.parse::<Contestant>().unwrap().to_string(), s);
+        }
+        for bad in ["", "depth", "depth:0", "depth:2:blind", "depth::lines", "mcts:x", "random:1", "engine:", "alphazero"] {
+            assert_eq!(bad.parse::<Contestant>(), Err(ParseContestantError), "{bad}");
+        }
+    }
+}
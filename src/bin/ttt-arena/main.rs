@@ -0,0 +1,43 @@
+//! Plays two contestants against each other and reports how the first one
+//! fared, to tell whether a change to the AI made it any stronger.
+
+mod arena;
+mod contestant;
+mod stats;
+
+use std::env;
+
+use anyhow::{bail, Context as _};
+
+use crate::arena::{results, Arena, MAX_OPENING};
+
+const USAGE: &str = "usage: ttt-arena <a> <b> [--games N] [--seed N] [--openings PLIES]
+contestants: minimax, depth:N, depth:N:lines, mcts:N, random, engine:CMD";
+
+fn main() -> anyhow::Result<()> {
+    let mut args = env::args().skip(1);
+    let a = args.next().context(USAGE)?.parse()?;
+    let b = args.next().context(USAGE)?.parse()?;
+
+    let mut arena = Arena { a, b, games: 100, seed: 0, openings: 2 };
+    while let Some(flag) = args.next() {
+        let value = args.next().context(USAGE)?;
+        match flag.as_str() {
+            "--games" => arena.games = value.parse()?,
+            "--seed" => arena.seed = value.parse()?,
+            "--openings" => arena.openings = value.parse()?,
+            _ => bail!(USAGE)
+        }
+    }
+    if arena.games == 0 {
+        bail!("there must be at least one game");
+    }
+    if arena.openings > MAX_OPENING {
+        bail!("openings can be at most {MAX_OPENING} plies");
+    }
+
+    println!("{} vs {}, seed {}, {} random plies", arena.a, arena.b, arena.seed, arena.openings);
+    let games = arena.run()?;
+    println!("{}", results(&games));
+    Ok(())
+}
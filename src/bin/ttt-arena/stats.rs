@@ -0,0 +1,89 @@
+use std::fmt::Display;
+
+/// Two-sided 95% quantile of the normal distribution.
+const Z95: f64 = 1.96;
+
+/// Results from the first contestant's point of view.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Results {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32
+}
+
+impl Results {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Points per game, a draw being worth half a win.
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.) / self.games() as f64
+    }
+
+    /// The 95% confidence interval of [`Self::score`], by Wilson's score
+    /// interval. Games come in pairs from the same opening, which may well
+    /// end alike, so only the pairs count as independent. A pair's score can
+    /// vary no more than a coin flip's, so this holds however they went.
+    pub fn interval(&self) -> (f64, f64) {
+        let n = self.games() as f64 / 2.;
+        let s = self.score();
+        let z2 = Z95 * Z95;
+        let center = (s + z2 / (2. * n)) / (1. + z2 / n);
+        let half = Z95 * (s * (1. - s) / n + z2 / (4. * n * n)).sqrt() / (1. + z2 / n);
+        ((center - half).max(0.), (center + half).min(1.))
+    }
+
+    /// The Elo difference that the score implies, with its 95% confidence
+    /// interval. Infinite when one side won or lost everything.
+    pub fn elo(&self) -> (f64, f64, f64) {
+        let (lo, hi) = self.interval();
+        (elo(self.score()), elo(lo), elo(hi))
+    }
+}
+
+fn elo(score: f64) -> f64 {
+    400. * (score / (1. - score)).log10()
+}
+
+impl Display for Results {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (lo, hi) = self.interval();
+        let (elo, elo_lo, elo_hi) = self.elo();
+        writeln!(f, "games {}: +{} ={} -{}", self.games(), self.wins, self.draws, self.losses)?;
+        writeln!(f, "score {:.1}% [{:.1}%, {:.1}%]", 100. * self.score(), 100. * lo, 100. * hi)?;
+        write!(f, "elo {elo:+.0} [{elo_lo:+.0}, {elo_hi:+.0}]")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn even_results_center_on_zero() {
+        let results = Results { wins: 30, draws: 40, losses: 30 };
+        assert_eq!(results.score(), 0.5);
+        let (lo, hi) = results.interval();
+        assert!((lo + hi - 1.).abs() < 1e-12 && lo > 0.3 && hi < 0.7);
+        let (elo, lo, hi) = results.elo();
+        assert!(elo == 0. && (lo + hi).abs() < 1e-9 && hi > 0.);
+    }
+
+    #[test]
+    fn a_few_draws_prove_little() {
+        let results = Results { wins: 0, draws: 10, losses: 0 };
+        let (lo, hi) = results.interval();
+        assert!(lo < 0.3 && hi > 0.7, "[{lo}, {hi}]");
+        assert_eq!(results.to_string(), "games 10: +0 =10 -0\nscore 50.0% [17.0%, 83.0%]\nelo +0 [-275, +275]");
+        // More of the same narrows it down.
+        let (more_lo, _) = Results { wins: 0, draws: 1000, losses: 0 }.interval();
+        assert!(lo < more_lo && more_lo < 0.5);
+    }
+
+    #[test]
+    fn a_clean_sweep_is_only_unbounded_above() {
+        let (elo, lo, hi) = Results { wins: 8, draws: 0, losses: 0 }.elo();
+        assert!(elo == f64::INFINITY && lo.is_finite() && lo > 0. && hi == f64::INFINITY);
+    }
+}
@@ -0,0 +1,21 @@
+//! Hosts games for any number of clients over TCP, speaking JSON one object
+//! per line. See `protocol` for the messages.
+
+mod protocol;
+mod server;
+
+use std::{env, net::TcpListener, sync::Arc};
+
+use anyhow::Context as _;
+
+use crate::server::Server;
+
+const DEFAULT_ADDR: &str = "127.0.0.1:7878";
+
+fn main() -> anyhow::Result<()> {
+    let addr = env::args().nth(1).unwrap_or_else(|| DEFAULT_ADDR.to_owned());
+    let listener = TcpListener::bind(&addr).with_context(|| addr.clone())?;
+    eprintln!("listening on {}", listener.local_addr()?);
+    Arc::new(Server::default()).serve(listener)?;
+    Ok(())
+}
@@ -0,0 +1,68 @@
+//! What clients and the server say to each other: one JSON object per line,
+//! tagged with its `type`.
+
+use serde::{Deserialize, Serialize};
+use ttt::game::Player;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Side {
+    X,
+    O
+}
+
+impl From<Player> for Side {
+    fn from(p: Player) -> Self {
+        match p {
+            Player::X => Side::X,
+            Player::O => Side::O
+        }
+    }
+}
+
+impl From<Side> for Player {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::X => Player::X,
+            Side::O => Player::O
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Opponent {
+    /// Whoever asks next.
+    #[default]
+    Human,
+    Ai
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    /// Asks for a game. Against the AI, it may carry on from `moves`, written
+    /// as in a record, with the client playing `side`, by default the side
+    /// to move.
+    Play {
+        #[serde(default)]
+        opponent: Opponent,
+        #[serde(default)]
+        moves: Option<String>,
+        #[serde(default)]
+        side: Option<Side>
+    },
+    Move { cell: String }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Reply {
+    /// Queued until another client wants a game.
+    Waiting,
+    Start { game: u64, side: Side, opponent: Opponent, moves: String },
+    /// Sent to both players, including the one who moved.
+    Moved { player: Side, cell: String, ply: usize },
+    /// `winner` is null for a draw. A forfeit means the loser left.
+    Over { winner: Option<Side>, forfeit: bool },
+    Error { message: String }
+}
@@ -0,0 +1,457 @@
+//! Pairs clients with each other or with the AI and referees their games,
+//! any number at once. Each client gets a thread reading its requests and
+//! one writing its replies. Games live in memory while anyone plays them.
+//!
+//! A game has at most one AI search going. The searches share the rayon
+//! pool a slice at a time, each going to the back of the line when its slice
+//! is up, so a game with a hard position only slows the others down rather
+//! than stopping them. A search ends early if its game does.
+
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc,
+        Arc,
+        Mutex
+    },
+    thread,
+    time::{Duration, Instant}
+};
+
+use ttt::{
+    ai::maximize_until,
+    game::{cell_name, parse_cell, Player, Record, Score, State}
+};
+
+use crate::protocol::{Opponent, Reply, Request};
+
+type Shared<T> = Arc<Mutex<T>>;
+
+/// How long an AI search runs before making way for the next in line.
+const SLICE: Duration = Duration::from_millis(20);
+
+struct Client {
+    replies: mpsc::Sender<Reply>,
+    /// The game the client is in and their side in it.
+    seat: Mutex<Option<(Shared<Game>, Player)>>
+}
+
+impl Client {
+    fn send(&self, reply: Reply) {
+        // The writer only stops once the connection is gone.
+        let _ = self.replies.send(reply);
+    }
+
+    fn error(&self, message: impl Into<String>) {
+        self.send(Reply::Error { message: message.into() });
+    }
+
+    /// Whether the client is in a game that isn't over yet.
+    fn playing(&self) -> bool {
+        self.seat.lock().unwrap().as_ref().is_some_and(|(g, _)| !g.lock().unwrap().is_over())
+    }
+}
+
+enum Seat {
+    Client(mpsc::Sender<Reply>),
+    Ai,
+    /// A client who left.
+    Empty
+}
+
+struct Game {
+    state: State,
+    record: Record,
+    /// X first.
+    seats: [Seat; 2],
+    /// Set when a player leaves, since the board can't tell.
+    forfeited: bool,
+    /// Set along with `forfeited`, to call off the AI's search.
+    cancel: Arc<AtomicBool>
+}
+
+impl Game {
+    fn is_over(&self) -> bool {
+        self.forfeited || self.state.score().is_some()
+    }
+
+    fn broadcast(&self, reply: Reply) {
+        for seat in &self.seats {
+            if let Seat::Client(tx) = seat {
+                let _ = tx.send(reply.clone());
+            }
+        }
+    }
+}
+
+/// Makes the `ply`th move of `game` for whoever is to move, unless the game
+/// has moved on, and sets the AI thinking if it is next.
+fn play(game: &Shared<Game>, ply: usize, x: u8, y: u8) -> Result<(), &'static str> {
+    let mut g = game.lock().unwrap();
+    if g.is_over() || g.record.len() + 1 != ply {
+        return Err("the game has moved on")
+    }
+    let player = g.state.turn().unwrap();
+    g.state = g.state.do_move(x, y).map_err(|_| "illegal move")?;
+    g.record.push(x, y);
+    g.broadcast(Reply::Moved { player: player.into(), cell: cell_name((x, y)), ply });
+
+    match (g.state.score(), g.state.turn()) {
+        (Some(score), _) => g.broadcast(Reply::Over {
+            winner: match score {
+                Score::Win(p) => Some(p.into()),
+                Score::Tie => None
+            },
+            forfeit: false
+        }),
+        (None, Some(p)) if matches!(g.seats[p as usize], Seat::Ai) => {
+            let search = Search { game: game.clone(), cancel: g.cancel.clone(), st: g.state, p, ply: g.record.len() + 1 };
+            drop(g);
+            think(search);
+        },
+        _ => ()
+    }
+    Ok(())
+}
+
+/// An AI search waiting for its next slice.
+struct Search {
+    game: Shared<Game>,
+    cancel: Arc<AtomicBool>,
+    st: State,
+    p: Player,
+    ply: usize
+}
+
+/// Searches in the order they get their slices, at most one per game.
+static SEARCHES: Mutex<VecDeque<Search>> = Mutex::new(VecDeque::new());
+
+/// Queues a search for the AI's move, to run a slice at a time, going to
+/// the back of the line after each until it has one or is called off. Rayon
+/// would run a job spawned from its own pool ahead of the others, so each
+/// job takes whichever search is at the front instead.
+fn think(search: Search) {
+    SEARCHES.lock().unwrap().push_back(search);
+    rayon::spawn(|| {
+        let search = SEARCHES.lock().unwrap().pop_front().unwrap();
+        let end = Instant::now() + SLICE;
+        let stop = || search.cancel.load(Ordering::Relaxed) || Instant::now() >= end;
+        match maximize_until(search.st, search.p, stop) {
+            Some((_, pos)) => {
+                let (x, y) = pos.unwrap();
+                // Fails if the opponent left meanwhile, which is fine.
+                let _ = play(&search.game, search.ply, x, y);
+            },
+            None if search.cancel.load(Ordering::Relaxed) => (),
+            None => think(search)
+        }
+    });
+}
+
+#[derive(Default)]
+pub struct Server {
+    next_game: AtomicU64,
+    /// A client waiting for a human opponent.
+    waiting: Mutex<Option<Arc<Client>>>
+}
+
+impl Server {
+    pub fn serve(self: Arc<Self>, listener: TcpListener) -> io::Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let server = self.clone();
+            thread::spawn(move || server.handle(stream));
+        }
+        Ok(())
+    }
+
+    fn handle(&self, stream: TcpStream) -> io::Result<()> {
+        let (replies, rx) = mpsc::channel();
+        let mut writer = stream.try_clone()?;
+        thread::spawn(move || {
+            for reply in rx {
+                if writeln!(writer, "{}", serde_json::to_string(&reply).unwrap()).is_err() {
+                    break
+                }
+            }
+        });
+
+        let client = Arc::new(Client { replies, seat: Mutex::new(None) });
+        for line in BufReader::new(stream).lines() {
+            let Ok(line) = line else { break };
+            match serde_json::from_str(&line) {
+                Ok(req) => self.request(&client, req),
+                Err(e) => client.error(e.to_string())
+            }
+        }
+        self.leave(&client);
+        Ok(())
+    }
+
+    fn request(&self, client: &Arc<Client>, req: Request) {
+        match req {
+            Request::Play { .. } if client.playing() => client.error("already playing"),
+            Request::Play { opponent: Opponent::Human, moves: None, side: None } => self.pair(client),
+            Request::Play { opponent: Opponent::Human, .. } => client.error("only games against the AI can be set up"),
+            Request::Play { opponent: Opponent::Ai, moves, side } => {
+                let Ok(record) = moves.as_deref().unwrap_or("").parse::<Record>() else {
+                    return client.error("invalid move list")
+                };
+                let state = record.state_at(record.len());
+                let Some(turn) = state.turn() else { return client.error("the game is already over") };
+                let side = side.map_or(turn, Player::from);
+
+                self.stop_waiting(client);
+                let mut seats = [Seat::Ai, Seat::Ai];
+                seats[side as usize] = Seat::Client(client.replies.clone());
+                self.start(state, record, seats, &[(client, side)]);
+            },
+            Request::Move { cell } => {
+                let seat = client.seat.lock().unwrap().clone();
+                let Some((game, side)) = seat else { return client.error("not in a game") };
+                let Some((x, y)) = parse_cell(&cell) else { return client.error("invalid cell") };
+                let ply = {
+                    let g = game.lock().unwrap();
+                    if g.is_over() || g.state.turn() != Some(side) {
+                        return client.error("not your turn")
+                    }
+                    g.record.len() + 1
+                };
+                if let Err(e) = play(&game, ply, x, y) {
+                    client.error(e);
+                }
+            }
+        }
+    }
+
+    /// Starts a game with whoever is waiting, or waits for someone. Someone
+    /// who has started another game since is passed over.
+    fn pair(&self, client: &Arc<Client>) {
+        let mut waiting = self.waiting.lock().unwrap();
+        match waiting.take() {
+            Some(other) if !Arc::ptr_eq(&other, client) && !other.playing() => {
+                drop(waiting);
+                let seats = [Seat::Client(other.replies.clone()), Seat::Client(client.replies.clone())];
+                self.start(State::default(), Record::default(), seats, &[(&other, Player::X), (client, Player::O)]);
+            },
+            _ => {
+                *waiting = Some(client.clone());
+                client.send(Reply::Waiting);
+            }
+        }
+    }
+
+    fn start(&self, state: State, record: Record, seats: [Seat; 2], clients: &[(&Arc<Client>, Player)]) {
+        let id = self.next_game.fetch_add(1, Ordering::Relaxed);
+        let ai = matches!(seats, [Seat::Ai, _] | [_, Seat::Ai]);
+        let moves = record.to_string();
+        let ply = record.len() + 1;
+        let cancel = Arc::new(AtomicBool::new(false));
+        let game = Arc::new(Mutex::new(Game { state, record, seats, forfeited: false, cancel: cancel.clone() }));
+
+        for &(client, side) in clients {
+            *client.seat.lock().unwrap() = Some((game.clone(), side));
+            let opponent = if ai { Opponent::Ai } else { Opponent::Human };
+            client.send(Reply::Start { game: id, side: side.into(), opponent, moves: moves.clone() });
+        }
+
+        let turn = state.turn().unwrap();
+        if matches!(game.lock().unwrap().seats[turn as usize], Seat::Ai) {
+            think(Search { game, cancel, st: state, p: turn, ply });
+        }
+    }
+
+    fn stop_waiting(&self, client: &Arc<Client>) {
+        let mut waiting = self.waiting.lock().unwrap();
+        if waiting.as_ref().is_some_and(|w| Arc::ptr_eq(w, client)) {
+            *waiting = None;
+        }
+    }
+
+    /// Forgets a client that disconnected. Their opponent wins by forfeit.
+    fn leave(&self, client: &Arc<Client>) {
+        self.stop_waiting(client);
+
+        let Some((game, side)) = client.seat.lock().unwrap().take() else { return };
+        let mut g = game.lock().unwrap();
+        if !g.is_over() {
+            g.forfeited = true;
+            g.cancel.store(true, Ordering::Relaxed);
+            g.seats[side as usize] = Seat::Empty;
+            g.broadcast(Reply::Over { winner: Some(side.other().into()), forfeit: true });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, time::Duration};
+
+    use super::*;
+    use crate::protocol::Side;
+
+    fn start_server() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || Arc::new(Server::default()).serve(listener));
+        addr
+    }
+
+    struct TestClient {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream
+    }
+
+    impl TestClient {
+        fn connect(addr: SocketAddr) -> Self {
+            let writer = TcpStream::connect(addr).unwrap();
+            Self { reader: BufReader::new(writer.try_clone().unwrap()), writer }
+        }
+
+        fn send(&mut self, line: &str) {
+            writeln!(self.writer, "{line}").unwrap();
+        }
+
+        fn recv(&mut self) -> Reply {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).unwrap();
+            serde_json::from_str(&line).unwrap()
+        }
+
+        fn play(&mut self, cell: &str) {
+            self.send(&format!(r#"{{"type":"move","cell":"{cell}"}}"#));
+        }
+    }
+
+    /// Two clients that asked for a human opponent, X first.
+    fn paired(addr: SocketAddr) -> (TestClient, TestClient) {
+        let (mut x, mut o) = (TestClient::connect(addr), TestClient::connect(addr));
+        x.send(r#"{"type":"play"}"#);
+        assert_eq!(x.recv(), Reply::Waiting);
+        o.send(r#"{"type":"play","opponent":"human"}"#);
+        let (Reply::Start { game: a, side: Side::X, .. }, Reply::Start { game: b, side: Side::O, .. }) = (x.recv(), o.recv()) else {
+            panic!("not started")
+        };
+        assert_eq!(a, b);
+        (x, o)
+    }
+
+    fn moved(player: Side, cell: &str, ply: usize) -> Reply {
+        Reply::Moved { player, cell: cell.to_owned(), ply }
+    }
+
+    #[test]
+    fn paired_clients_play_a_refereed_game() {
+        let addr = start_server();
+        let (mut x, mut o) = paired(addr);
+
+        o.play("a1");
+        assert_eq!(o.recv(), Reply::Error { message: "not your turn".to_owned() });
+
+        // X takes the top row while O dawdles on the second.
+        for (ply, cell) in ["a1", "a2", "b1", "b2", "c1", "c2", "d1"].into_iter().enumerate() {
+            let (mover, side) = if ply % 2 == 0 { (&mut x, Side::X) } else { (&mut o, Side::O) };
+            mover.play(cell);
+            assert_eq!(x.recv(), moved(side, cell, ply + 1));
+            assert_eq!(o.recv(), moved(side, cell, ply + 1));
+        }
+
+        let over = Reply::Over { winner: Some(Side::X), forfeit: false };
+        assert_eq!((x.recv(), o.recv()), (over.clone(), over));
+    }
+
+    #[test]
+    fn games_run_side_by_side() {
+        let addr = start_server();
+        let (mut x1, mut o1) = paired(addr);
+        let (mut x2, mut o2) = paired(addr);
+
+        x2.play("d4");
+        x1.play("a1");
+        assert_eq!(o1.recv(), moved(Side::X, "a1", 1));
+        assert_eq!(o2.recv(), moved(Side::X, "d4", 1));
+        o1.play("d4");
+        assert_eq!(x1.recv(), moved(Side::X, "a1", 1));
+        assert_eq!(x1.recv(), moved(Side::O, "d4", 2));
+    }
+
+    #[test]
+    fn ai_answers_from_a_set_up_position() {
+        let addr = start_server();
+        let mut c = TestClient::connect(addr);
+        c.send(r#"{"type":"play","opponent":"ai","moves":"a1 b1 c1 d1 a2 b2 c2 d2 b3 a3"}"#);
+        let Reply::Start { side: Side::X, opponent: Opponent::Ai, moves, .. } = c.recv() else { panic!("not started") };
+        assert_eq!(moves, "a1 b1 c1 d1 a2 b2 c2 d2 b3 a3");
+
+        c.play("c3");
+        assert_eq!(c.recv(), moved(Side::X, "c3", 11));
+        assert!(matches!(c.recv(), Reply::Moved { player: Side::O, ply: 12, .. }));
+    }
+
+    #[test]
+    fn leaving_forfeits() {
+        let addr = start_server();
+        let (mut x, o) = paired(addr);
+        drop(o);
+        assert_eq!(x.recv(), Reply::Over { winner: Some(Side::X), forfeit: true });
+
+        // X is free to play again.
+        x.send(r#"{"type":"play"}"#);
+        assert_eq!(x.recv(), Reply::Waiting);
+    }
+
+    #[test]
+    fn bad_requests_get_errors() {
+        let addr = start_server();
+        let mut c = TestClient::connect(addr);
+        for line in ["nonsense", r#"{"type":"move","cell":"a1"}"#, r#"{"type":"play","opponent":"ai","moves":"a1 a1"}"#] {
+            c.send(line);
+            assert!(matches!(c.recv(), Reply::Error { .. }), "{line}");
+        }
+
+        let (mut x, _o) = paired(addr);
+        x.play("e9");
+        assert_eq!(x.recv(), Reply::Error { message: "illegal move".to_owned() });
+    }
+
+    #[test]
+    fn a_hard_search_does_not_hold_up_other_games() {
+        let addr = start_server();
+        // Opening an empty board, the AI has the whole game to search.
+        let mut slow = TestClient::connect(addr);
+        slow.send(r#"{"type":"play","opponent":"ai","side":"O"}"#);
+        assert!(matches!(slow.recv(), Reply::Start { side: Side::O, .. }));
+
+        let mut fast = TestClient::connect(addr);
+        fast.reader.get_ref().set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+        fast.send(r#"{"type":"play","opponent":"ai","moves":"a1 b1 c1 d1 a2 b2 c2 d2 b3 a3"}"#);
+        assert!(matches!(fast.recv(), Reply::Start { .. }));
+        fast.play("c3");
+        assert_eq!(fast.recv(), moved(Side::X, "c3", 11));
+        assert!(matches!(fast.recv(), Reply::Moved { player: Side::O, ply: 12, .. }));
+
+        // Leaving calls off the search, rather than have it run on through
+        // the other tests.
+        drop(slow);
+    }
+
+    #[test]
+    fn asking_for_the_ai_stops_waiting() {
+        let addr = start_server();
+        let mut a = TestClient::connect(addr);
+        a.send(r#"{"type":"play"}"#);
+        assert_eq!(a.recv(), Reply::Waiting);
+        a.send(r#"{"type":"play","opponent":"ai","moves":"a1 b1 c1 d1 a2 b2 c2 d2 b3 a3"}"#);
+        assert!(matches!(a.recv(), Reply::Start { opponent: Opponent::Ai, .. }));
+
+        // Nobody is left waiting, so the next two play each other.
+        let (_x, _o) = paired(addr);
+
+        a.play("c3");
+        assert_eq!(a.recv(), moved(Side::X, "c3", 11));
+        assert!(matches!(a.recv(), Reply::Moved { player: Side::O, ply: 12, .. }));
+    }
+}
@@ -0,0 +1,111 @@
+use std::{fs, io::{self, BufWriter}, path::Path, time::Duration};
+
+use anyhow::{bail, Context as _};
+
+use crate::{
+    engine,
+    export,
+    game::{Record, State},
+    load_theme,
+    rend::{Renderer, Scene},
+    theme::Theme
+};
+
+const DEFAULT_SIZE: (u32, u32) = (600, 600);
+const DEFAULT_DELAY: Duration = Duration::from_millis(800);
+
+pub fn run(cmd: &str, args: impl Iterator<Item = String>) -> anyhow::Result<()> {
+    match cmd {
+        "render" => render(args),
+        "export" => export(args),
+        "engine" => Ok(engine::serve(io::stdin().lock(), io::stdout().lock())?),
+        _ => bail!("unknown command `{cmd}`")
+    }
+}
+
+/// Accepts a preset name or the path of a theme file.
+fn theme_arg(arg: &str) -> anyhow::Result<Theme> {
+    if let Some(theme) = Theme::preset(arg) {
+        return Ok(theme);
+    }
+
+    let src = fs::read_to_string(arg).with_context(|| format!("{arg}: not a preset or readable theme file"))?;
+    Theme::parse(&src).with_context(|| arg.to_owned())
+}
+
+fn size_arg(arg: &str) -> anyhow::Result<(u32, u32)> {
+    let (w, h) = arg.split_once('x').context("size must be WIDTHxHEIGHT")?;
+    Ok((w.parse()?, h.parse()?))
+}
+
+fn render(mut args: impl Iterator<Item = String>) -> anyhow::Result<()> {
+    const USAGE: &str = "usage: ttt render <position> <out.png|out.svg> [--size WxH] [--theme NAME|FILE]";
+
+    let position = args.next().context(USAGE)?;
+    let out = args.next().context(USAGE)?;
+
+    let (mut w, mut h) = DEFAULT_SIZE;
+    let mut theme = None;
+    while let Some(flag) = args.next() {
+        let value = args.next().context(USAGE)?;
+        match flag.as_str() {
+            "--size" => (w, h) = size_arg(&value)?,
+            "--theme" => theme = Some(theme_arg(&value)?),
+            _ => bail!(USAGE)
+        }
+    }
+
+    let state = position.parse::<State>().with_context(|| position.clone())?;
+
+    let mut rend = Renderer::default();
+    rend.set_theme(match theme {
+        Some(theme) => theme,
+        None => load_theme()?
+    });
+
+    let scene = Scene::from(state);
+    if Path::new(&out).extension().is_some_and(|e| e == "svg") {
+        return fs::write(&out, rend.render_svg(&scene)).with_context(|| out.clone());
+    }
+
+    let pixmap = rend.render_offscreen(&scene, w, h).context("size must be non-zero")?;
+    pixmap.save_png(&out).with_context(|| out.clone())?;
+    Ok(())
+}
+
+fn export(mut args: impl Iterator<Item = String>) -> anyhow::Result<()> {
+    const USAGE: &str = "usage: ttt export <moves> <out.gif|out.svg> [--size WxH] [--theme NAME|FILE] [--delay MS]";
+
+    let moves = args.next().context(USAGE)?;
+    let out = args.next().context(USAGE)?;
+
+    let mut size = DEFAULT_SIZE;
+    let mut delay = DEFAULT_DELAY;
+    let mut theme = None;
+    while let Some(flag) = args.next() {
+        let value = args.next().context(USAGE)?;
+        match flag.as_str() {
+            "--size" => size = size_arg(&value)?,
+            "--theme" => theme = Some(theme_arg(&value)?),
+            "--delay" => delay = Duration::from_millis(value.parse()?),
+            _ => bail!(USAGE)
+        }
+    }
+
+    let record = moves.parse::<Record>().with_context(|| moves.clone())?;
+
+    let mut rend = Renderer::default();
+    rend.set_theme(match theme {
+        Some(theme) => theme,
+        None => load_theme()?
+    });
+
+    match Path::new(&out).extension().and_then(|e| e.to_str()) {
+        Some("gif") => {
+            let file = fs::File::create(&out).with_context(|| out.clone())?;
+            export::gif(&mut rend, &record, size, delay, BufWriter::new(file))
+        },
+        Some("svg") => fs::write(&out, export::svg(&mut rend, &record, delay)).with_context(|| out.clone()),
+        _ => bail!("{out}: expected a .gif or .svg file")
+    }
+}
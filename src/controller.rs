@@ -0,0 +1,417 @@
+//! Runs a game for any frontend. The controller owns the position and its
+//! record, knows who moves for each player, runs the AI and keeps the clocks,
+//! and reports what happened as [`Event`]s for the frontend to show.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration
+};
+
+use crate::{
+    ai::{maximize, search},
+    engine::{External, Go},
+    executor::{Executor, Scope},
+    game::{InvalidMove, Player, Record, Score, State},
+    time_control::{Clocks, TimeControl},
+    timer::Timers,
+    unblock
+};
+
+/// How long the AI waits before answering a move, so that the answer doesn't
+/// land in the same frame.
+pub const AI_DELAY: Duration = Duration::from_millis(200);
+/// How long an engine may think without a clock, so that one that never
+/// answers is found out.
+const ENGINE_MOVETIME: Duration = Duration::from_secs(5);
+
+/// Who moves for a player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Seat {
+    Human,
+    Ai,
+    /// Someone at the other end of a connection, whose moves the frontend
+    /// passes on with [`GameController::play_remote`].
+    Remote,
+    /// The external engine given to [`GameController::set_engine`].
+    Engine
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// A game began, possibly part way through.
+    Started,
+    /// `player` moved at (`x`, `y`), bringing the record to `ply` moves.
+    Moved { player: Player, x: u8, y: u8, ply: usize },
+    /// The AI worked out the outcome with best play.
+    Evaluated(Score),
+    /// `player` ran out of time. [`Event::Over`] follows.
+    Flagged(Player),
+    /// `player`'s engine couldn't come up with a legal move, and the game is
+    /// stuck until the next one.
+    EngineFailed(Player),
+    Over(Score)
+}
+
+pub struct GameController {
+    exec: Executor<GameController>,
+    timers: Timers,
+    /// The AI's search and the flag falling, cancelled once the turn is over.
+    turn: Scope,
+    seats: [Seat; 2],
+    engine: Option<Arc<Mutex<External>>>,
+    control: Option<TimeControl>,
+    clocks: Option<Clocks>,
+    state: State,
+    record: Record,
+    flagged: Option<Player>,
+    eval: Option<Score>,
+    events: VecDeque<Event>
+}
+
+impl GameController {
+    /// Nothing happens until a game is started.
+    pub fn new(exec: Executor<GameController>, timers: Timers, seats: [Seat; 2]) -> Self {
+        Self {
+            exec,
+            timers,
+            turn: Scope::default(),
+            seats,
+            engine: None,
+            control: None,
+            clocks: None,
+            state: State::default(),
+            record: Record::default(),
+            flagged: None,
+            eval: None,
+            events: VecDeque::new()
+        }
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    pub fn record(&self) -> &Record {
+        &self.record
+    }
+
+    pub fn eval(&self) -> Option<Score> {
+        self.eval
+    }
+
+    pub fn seat(&self, player: Player) -> Seat {
+        self.seats[player as usize]
+    }
+
+    /// Takes effect from the next game on.
+    pub fn set_seats(&mut self, seats: [Seat; 2]) {
+        self.seats = seats;
+    }
+
+    /// Takes effect from the next game on.
+    pub fn set_engine(&mut self, engine: Option<External>) {
+        self.engine = engine.map(|e| Arc::new(Mutex::new(e)));
+    }
+
+    /// Takes effect from the next game on.
+    pub fn set_time_control(&mut self, control: Option<TimeControl>) {
+        self.control = control;
+    }
+
+    /// Both players' time left, X first, if the game is played on time.
+    pub fn clocks(&self) -> Option<[Duration; 2]> {
+        self.clocks.as_ref().map(|c| c.both(self.timers.now()))
+    }
+
+    /// The player who lost on time, if the game ended that way.
+    pub fn flagged(&self) -> Option<Player> {
+        self.flagged
+    }
+
+    /// How the game ended, on the board or on time.
+    pub fn outcome(&self) -> Option<Score> {
+        self.flagged.map(|p| Score::Win(p.other())).or(self.state.score())
+    }
+
+    /// Whether a human is to move.
+    pub fn awaiting_human(&self) -> bool {
+        self.to_move().is_some_and(|p| self.seat(p) == Seat::Human)
+    }
+
+    fn to_move(&self) -> Option<Player> {
+        self.state.turn().filter(|_| self.outcome().is_none())
+    }
+
+    pub fn new_game(&mut self) {
+        self.start(Record::default());
+    }
+
+    /// Abandons the current game and carries on from `record`.
+    pub fn start(&mut self, record: Record) {
+        self.turn.reset();
+        self.state = record.state_at(record.len());
+        self.record = record;
+        self.flagged = None;
+        self.eval = None;
+        self.clocks = self.control.map(Clocks::new);
+        self.events.push_back(Event::Started);
+        self.next_turn(Duration::ZERO);
+    }
+
+    /// Plays a human's move. Fails if it is someone else's turn or the move
+    /// isn't legal.
+    pub fn play(&mut self, x: u8, y: u8) -> Result<(), InvalidMove> {
+        self.play_as(Seat::Human, x, y)
+    }
+
+    /// Like [`Self::play`], for a remote player's move.
+    pub fn play_remote(&mut self, x: u8, y: u8) -> Result<(), InvalidMove> {
+        self.play_as(Seat::Remote, x, y)
+    }
+
+    fn play_as(&mut self, seat: Seat, x: u8, y: u8) -> Result<(), InvalidMove> {
+        if self.to_move().is_none_or(|p| self.seat(p) != seat) {
+            return Err(InvalidMove)
+        }
+        self.apply_move(x, y)?;
+        self.next_turn(AI_DELAY);
+        Ok(())
+    }
+
+    /// Applies the callbacks of the controller's finished tasks. The
+    /// frontend calls this whenever it has run some of them.
+    pub fn run_completions(&mut self) {
+        while let Some(cb) = self.exec.next_completion() {
+            cb(self);
+        }
+    }
+
+    pub fn next_event(&mut self) -> Option<Event> {
+        self.events.pop_front()
+    }
+
+    fn apply_move(&mut self, x: u8, y: u8) -> Result<(), InvalidMove> {
+        let now = self.timers.now();
+        let player = self.to_move().ok_or(InvalidMove)?;
+        let st = self.state.do_move(x, y)?;
+        // A move that comes as the flag falls is too late.
+        if self.clocks.as_ref().is_some_and(|c| c.flagged(now).is_some()) {
+            self.flag(player);
+            return Err(InvalidMove)
+        }
+        if let Some(clocks) = &mut self.clocks {
+            clocks.stop(now);
+        }
+
+        self.turn.reset();
+        self.state = st;
+        self.record.push(x, y);
+        self.events.push_back(Event::Moved { player, x, y, ply: self.record.len() });
+        if let Some(score) = self.state.score() {
+            self.events.push_back(Event::Over(score));
+        }
+        Ok(())
+    }
+
+    fn flag(&mut self, player: Player) {
+        self.turn.reset();
+        if let Some(clocks) = &mut self.clocks {
+            clocks.stop(self.timers.now());
+        }
+        self.flagged = Some(player);
+        self.events.push_back(Event::Flagged(player));
+        self.events.push_back(Event::Over(Score::Win(player.other())));
+    }
+
+    fn next_turn(&mut self, delay: Duration) {
+        let Some(p) = self.to_move() else { return };
+        if let Some(clocks) = &mut self.clocks {
+            clocks.start(p, self.timers.now());
+            let timer = self.timers.at(clocks.deadline().unwrap());
+            self.exec.spawn_scoped(&self.turn, timer, move |this, ()| this.flag(p));
+        }
+        if matches!(self.seat(p), Seat::Ai | Seat::Engine) {
+            self.start_ai(p, delay);
+        }
+    }
+
+    fn start_ai(&mut self, p: Player, delay: Duration) {
+        let st = self.state;
+        let record = self.record.clone();
+        let engine = (self.seat(p) == Seat::Engine).then(|| self.engine.clone());
+        let moves_left = st.succs().count().div_ceil(2) as u32;
+        let budget = self.clocks.as_ref().map(|c| c.budget(p, self.timers.now(), moves_left));
+        // Started before the search, so a slow search doesn't add to it, and
+        // never longer than the AI can afford.
+        let timer = self.timers.after(budget.map_or(delay, |b| delay.min(b)));
+        self.exec.spawn_scoped(
+            &self.turn,
+            async move {
+                let found = unblock(move || {
+                    if let Some(engine) = engine {
+                        let go = Go { movetime: Some(budget.unwrap_or(ENGINE_MOVETIME)), ..Go::default() };
+                        let (pos, line) = engine?.lock().unwrap().go(&record, go).ok()?;
+                        return Some((line.and_then(|l| l.score), pos?))
+                    }
+                    let (score, pos) = match budget {
+                        Some(budget) => search(st, p, budget),
+                        None => {
+                            let (score, pos) = maximize(st, p);
+                            (Some(score), pos)
+                        }
+                    };
+                    Some((score, pos?))
+                }).await;
+                timer.await;
+                found
+            },
+            move |this, found| {
+                let Some((score, (x, y))) = found else {
+                    this.events.push_back(Event::EngineFailed(p));
+                    return
+                };
+                let at = this.events.len();
+                match this.apply_move(x, y) {
+                    Ok(()) => {
+                        // Only a move that counted says anything about the
+                        // game, but the evaluation still reads best ahead of
+                        // it.
+                        if let Some(score) = score {
+                            let eval = match score {
+                                1 => Score::Win(p),
+                                -1 => Score::Win(p.other()),
+                                _ => Score::Tie
+                            };
+                            this.eval = Some(eval);
+                            this.events.insert(at, Event::Evaluated(eval));
+                        }
+                        this.next_turn(AI_DELAY)
+                    },
+                    // Unless the time ran out meanwhile, the engine is to
+                    // blame.
+                    Err(InvalidMove) if this.outcome().is_none() => this.events.push_back(Event::EngineFailed(p)),
+                    Err(InvalidMove) => ()
+                }
+            }
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{iter, time::Instant};
+
+    use super::*;
+    use crate::driver::Driver;
+
+    /// Ten moves in with nobody through, and X to move.
+    const MIDGAME: &str = "a1 b1 c1 d1 a2 b2 c2 d2 b3 a3";
+
+    fn controller(driver: &Driver<GameController>, seats: [Seat; 2]) -> GameController {
+        GameController::new(driver.exec.clone(), driver.timers.clone(), seats)
+    }
+
+    fn events(ctl: &mut GameController) -> Vec<Event> {
+        iter::from_fn(|| ctl.next_event()).collect()
+    }
+
+    #[test]
+    fn ai_answers_after_its_delay() {
+        let driver = Driver::new();
+        let mut ctl = controller(&driver, [Seat::Human, Seat::Ai]);
+        ctl.start(MIDGAME.parse().unwrap());
+        assert_eq!(events(&mut ctl), [Event::Started]);
+
+        ctl.play(2, 2).unwrap();
+        assert_eq!(ctl.play(3, 2), Err(InvalidMove));
+        driver.advance(&mut ctl, AI_DELAY - Duration::from_millis(1));
+        assert_eq!(ctl.record().len(), 11);
+
+        // The search runs on another thread, so it may still need a moment,
+        // but no more virtual time.
+        driver.advance(&mut ctl, Duration::from_millis(1));
+        driver.run_until(&mut ctl, |ctl| ctl.record().len() == 12);
+        assert!(matches!(
+            events(&mut ctl)[..],
+            [Event::Moved { player: Player::X, x: 2, y: 2, ply: 11 }, Event::Evaluated(_), Event::Moved { player: Player::O, ply: 12, .. }, ..]
+        ));
+        assert!(ctl.awaiting_human() || ctl.state().score().is_some());
+    }
+
+    #[test]
+    fn starting_over_discards_the_ai_move() {
+        let driver = Driver::new();
+        let mut ctl = controller(&driver, [Seat::Human, Seat::Ai]);
+        ctl.start(MIDGAME.parse().unwrap());
+        ctl.play(2, 2).unwrap();
+        driver.run_until_stalled(&mut ctl);
+
+        ctl.start(MIDGAME.parse().unwrap());
+        driver.advance(&mut ctl, AI_DELAY);
+        assert_eq!(ctl.record().len(), 10);
+        assert_eq!(events(&mut ctl).last(), Some(&Event::Started));
+    }
+
+    #[test]
+    fn ai_against_ai_plays_out() {
+        let driver = Driver::new();
+        let mut ctl = controller(&driver, [Seat::Ai, Seat::Ai]);
+        ctl.start(MIDGAME.parse().unwrap());
+        assert_eq!(ctl.play(2, 2), Err(InvalidMove));
+
+        while ctl.state().score().is_none() {
+            let ply = ctl.record().len();
+            driver.advance(&mut ctl, AI_DELAY);
+            driver.run_until(&mut ctl, |ctl| ctl.record().len() > ply);
+        }
+        let score = ctl.state().score().unwrap();
+        assert_eq!(events(&mut ctl).last(), Some(&Event::Over(score)));
+        // Perfect play from either side can't do better than the evaluation.
+        assert_eq!(ctl.eval(), Some(score));
+    }
+
+    #[test]
+    fn running_out_of_time_loses() {
+        let driver = Driver::new();
+        let mut ctl = controller(&driver, [Seat::Human, Seat::Ai]);
+        ctl.set_time_control(Some(TimeControl::Total { base: Duration::from_secs(2), increment: Duration::from_secs(1) }));
+        ctl.start(MIDGAME.parse().unwrap());
+
+        driver.advance(&mut ctl, Duration::from_millis(1500));
+        ctl.play(2, 2).unwrap();
+        assert_eq!(ctl.clocks(), Some([Duration::from_millis(1500), Duration::from_secs(2)]));
+        driver.advance(&mut ctl, AI_DELAY);
+        // Nobody can force a win from here, so the game goes on.
+        driver.run_until(&mut ctl, GameController::awaiting_human);
+
+        driver.advance(&mut ctl, Duration::from_millis(1499));
+        assert_eq!(ctl.outcome(), None);
+        driver.advance(&mut ctl, Duration::from_millis(1));
+        assert_eq!(ctl.outcome(), Some(Score::Win(Player::O)));
+        assert_eq!(events(&mut ctl)[..].last_chunk(), Some(&[Event::Flagged(Player::X), Event::Over(Score::Win(Player::O))]));
+        assert_eq!(ctl.play(3, 3), Err(InvalidMove));
+    }
+
+    #[test]
+    fn search_keeps_to_its_budget() {
+        // Far too little to solve the empty board in.
+        let st = State::default();
+        let start = Instant::now();
+        let (score, pos) = search(st, Player::X, Duration::from_millis(50));
+        assert!(start.elapsed() < Duration::from_millis(500), "searched for {:?}", start.elapsed());
+        assert_eq!(score, None);
+        assert!(pos.is_some_and(|(x, y)| st.do_move(x, y).is_ok()));
+    }
+
+    #[test]
+    fn engine_seat_without_an_engine_fails() {
+        let driver = Driver::new();
+        let mut ctl = controller(&driver, [Seat::Engine, Seat::Human]);
+        ctl.start(MIDGAME.parse().unwrap());
+        driver.advance(&mut ctl, AI_DELAY);
+        driver.run_until(&mut ctl, |ctl| !ctl.events.is_empty() && ctl.events.back() != Some(&Event::Started));
+        assert_eq!(events(&mut ctl), [Event::Started, Event::EngineFailed(Player::X)]);
+        assert_eq!(ctl.record().len(), 10);
+    }
+}
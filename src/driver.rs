@@ -0,0 +1,128 @@
+//! Runs the executor and timers the way the event loop does, but on a
+//! virtual clock, so async code can be tested deterministically.
+
+use std::{sync::mpsc, time::Duration};
+
+use async_task::Runnable;
+
+use crate::{
+    executor::Executor,
+    timer::{Timers, VirtualClock}
+};
+
+pub struct Driver<C> {
+    pub exec: Executor<C>,
+    pub timers: Timers,
+    pub clock: VirtualClock,
+    runnables: mpsc::Receiver<Runnable>
+}
+
+impl<C: 'static> Driver<C> {
+    pub fn new() -> Self {
+        let (tx, runnables) = mpsc::channel();
+        let clock = VirtualClock::new();
+        Self {
+            exec: Executor::new(move |r| tx.send(r).unwrap()),
+            timers: Timers::new(clock.clone()),
+            clock,
+            runnables
+        }
+    }
+
+    /// Runs whatever is ready, applying callbacks as they come, until every
+    /// task is waiting on something.
+    pub fn run_until_stalled(&self, ctx: &mut C) {
+        while let Ok(r) = self.runnables.try_recv() {
+            r.run();
+            while let Some(cb) = self.exec.next_completion() {
+                cb(ctx);
+            }
+        }
+    }
+
+    /// Runs tasks until `done` holds, waiting for work on other threads
+    /// whenever they stall. The clock stands still meanwhile.
+    pub fn run_until(&self, ctx: &mut C, done: impl Fn(&C) -> bool) {
+        self.run_until_stalled(ctx);
+        while !done(ctx) {
+            let r = self.runnables.recv_timeout(Duration::from_secs(10)).expect("stalled for good");
+            r.run();
+            while let Some(cb) = self.exec.next_completion() {
+                cb(ctx);
+            }
+            self.run_until_stalled(ctx);
+        }
+    }
+
+    /// Moves the clock forward by `duration`, stopping at each deadline on
+    /// the way to fire it and run what it woke.
+    pub fn advance(&self, ctx: &mut C, duration: Duration) {
+        let end = self.timers.now() + duration;
+        self.run_until_stalled(ctx);
+        while let Some(at) = self.timers.next_deadline().filter(|&at| at <= end) {
+            self.clock.advance(at.saturating_duration_since(self.timers.now()));
+            self.timers.fire(at);
+            self.run_until_stalled(ctx);
+        }
+        self.clock.advance(end.saturating_duration_since(self.timers.now()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{future, pin::pin, task::Poll};
+
+    use super::*;
+    use crate::{PLACE_DURATION, progress};
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn animation_frames_tick_at_a_steady_rate() {
+        let driver = Driver::<Vec<f32>>::new();
+        let start = driver.timers.now();
+        let mut interval = driver.timers.interval(50 * MS);
+        let timers = driver.timers.clone();
+
+        // Records how far a placing animation would have got at each tick,
+        // stopping once it is done.
+        driver.exec.spawn_cb(async move {
+            let mut frames = Vec::new();
+            loop {
+                let due = interval.tick().await;
+                let t = progress(due, start, PLACE_DURATION);
+                frames.push(t);
+                if t >= 1. { break frames }
+            }
+        }, |out, frames| *out = frames).detach();
+
+        let mut frames = Vec::new();
+        driver.advance(&mut frames, Duration::from_secs(1));
+        assert_eq!(frames.len(), 5);
+        assert!(frames.is_sorted() && frames[4] == 1.);
+        // The loop is over, so its interval no longer holds a deadline.
+        assert_eq!(timers.next_deadline(), None);
+    }
+
+    #[test]
+    fn timeout_wins_the_race_against_a_stalled_task() {
+        let driver = Driver::<Vec<&str>>::new();
+        let timeout = driver.timers.after(Duration::from_secs(5));
+        driver.exec.spawn_cb(async move {
+            let mut work = pin!(future::pending::<()>());
+            let mut timeout = pin!(timeout);
+            future::poll_fn(|cx| {
+                if work.as_mut().poll(cx).is_ready() {
+                    return Poll::Ready("done")
+                }
+                timeout.as_mut().poll(cx).map(|()| "timed out")
+            }).await
+        }, |log, outcome| log.push(outcome)).detach();
+
+        let mut log = Vec::new();
+        driver.advance(&mut log, Duration::from_millis(4999));
+        assert!(log.is_empty());
+        driver.advance(&mut log, MS);
+        assert_eq!(log, ["timed out"]);
+    }
+}
@@ -0,0 +1,491 @@
+//! A line protocol for engines, after UCI. The GUI sends commands on the
+//! engine's stdin and the engine answers on its stdout:
+//!
+//! ```text
+//! > uci
+//! < id name ttt
+//! < uciok
+//! > position startpos moves a1 b2
+//! > go movetime 500
+//! < info depth 1 score unknown pv c3
+//! < info depth 2 score unknown pv c3 a2
+//! < bestmove c3
+//! ```
+//!
+//! Scores are from the point of view of the side to move: `win`, `loss`,
+//! `draw`, or `unknown` until proven. Lines that aren't understood are
+//! ignored on both sides, as are `go` parameters.
+
+use std::{
+    error::Error,
+    fmt::Display,
+    io::{self, BufRead, BufReader, Write},
+    process::{self, Child, ChildStdin, Stdio},
+    str::FromStr,
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant}
+};
+
+use crate::{
+    ai::{deepen, maximize, Limits, Line},
+    game::{cell_name, parse_cell, Player, Record}
+};
+
+const NAME: &str = "ttt";
+
+/// How long an external engine may take to introduce itself or get ready.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long past its `movetime` an external engine may take to answer.
+const MOVE_GRACE: Duration = Duration::from_secs(1);
+
+/// How long an engine may think about a move. Without any limits it thinks
+/// until it has solved the position.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Go {
+    pub depth: Option<u8>,
+    pub movetime: Option<Duration>,
+    /// Each side's time left, X first.
+    pub time: [Option<Duration>; 2],
+    /// Each side's increment, X first.
+    pub inc: [Duration; 2]
+}
+
+impl Go {
+    /// What `p` should limit its search to, with `moves_left` of its own
+    /// still to come at most.
+    pub fn limits(&self, p: Player, moves_left: u32) -> Limits {
+        let i = p as usize;
+        let time = self.movetime.or(self.time[i].map(|left| (left / moves_left.max(1) + self.inc[i]).min(left / 2)));
+        Limits { depth: self.depth, time, ..Limits::default() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Uci,
+    IsReady,
+    NewGame,
+    /// The position after these moves from the empty board.
+    Position(Record),
+    Go(Go),
+    Quit
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    Id(String),
+    UciOk,
+    ReadyOk,
+    Info(Line),
+    /// `None` if the game is already over.
+    BestMove(Option<(u8, u8)>)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseEngineMessageError;
+
+impl Display for ParseEngineMessageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("invalid engine protocol line")
+    }
+}
+
+impl Error for ParseEngineMessageError {}
+
+fn millis(s: Option<&str>) -> Result<Duration, ParseEngineMessageError> {
+    s.and_then(|s| s.parse().ok()).map(Duration::from_millis).ok_or(ParseEngineMessageError)
+}
+
+impl Display for Command {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Command::Uci => f.write_str("uci"),
+            Command::IsReady => f.write_str("isready"),
+            Command::NewGame => f.write_str("ucinewgame"),
+            Command::Position(record) if record.is_empty() => f.write_str("position startpos"),
+            Command::Position(record) => write!(f, "position startpos moves {record}"),
+            Command::Go(go) => {
+                f.write_str("go")?;
+                if let Some(depth) = go.depth {
+                    write!(f, " depth {depth}")?;
+                }
+                if let Some(movetime) = go.movetime {
+                    write!(f, " movetime {}", movetime.as_millis())?;
+                }
+                for (name, time) in ["xtime", "otime"].into_iter().zip(go.time) {
+                    if let Some(time) = time {
+                        write!(f, " {name} {}", time.as_millis())?;
+                    }
+                }
+                for (name, inc) in ["xinc", "oinc"].into_iter().zip(go.inc) {
+                    if !inc.is_zero() {
+                        write!(f, " {name} {}", inc.as_millis())?;
+                    }
+                }
+                Ok(())
+            },
+            Command::Quit => f.write_str("quit")
+        }
+    }
+}
+
+impl FromStr for Command {
+    type Err = ParseEngineMessageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        match words.next() {
+            Some("uci") => Ok(Command::Uci),
+            Some("isready") => Ok(Command::IsReady),
+            Some("ucinewgame") => Ok(Command::NewGame),
+            Some("position") => {
+                if words.next() != Some("startpos") {
+                    return Err(ParseEngineMessageError)
+                }
+                match words.next() {
+                    None => Ok(Command::Position(Record::default())),
+                    Some("moves") => words.collect::<Vec<_>>().join(" ").parse()
+                        .map(Command::Position)
+                        .map_err(|_| ParseEngineMessageError),
+                    Some(_) => Err(ParseEngineMessageError)
+                }
+            },
+            Some("go") => {
+                let mut go = Go::default();
+                while let Some(name) = words.next() {
+                    match name {
+                        "depth" => go.depth = Some(words.next().and_then(|d| d.parse().ok()).ok_or(ParseEngineMessageError)?),
+                        "movetime" => go.movetime = Some(millis(words.next())?),
+                        "xtime" => go.time[0] = Some(millis(words.next())?),
+                        "otime" => go.time[1] = Some(millis(words.next())?),
+                        "xinc" => go.inc[0] = millis(words.next())?,
+                        "oinc" => go.inc[1] = millis(words.next())?,
+                        _ => ()
+                    }
+                }
+                Ok(Command::Go(go))
+            },
+            Some("quit") => Ok(Command::Quit),
+            _ => Err(ParseEngineMessageError)
+        }
+    }
+}
+
+impl Display for Reply {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Reply::Id(name) => write!(f, "id name {name}"),
+            Reply::UciOk => f.write_str("uciok"),
+            Reply::ReadyOk => f.write_str("readyok"),
+            Reply::Info(line) => {
+                let score = match line.score {
+                    Some(1) => "win",
+                    Some(-1) => "loss",
+                    Some(_) => "draw",
+                    None => "unknown"
+                };
+                write!(f, "info depth {} score {score}", line.depth)?;
+                if !line.pv.is_empty() {
+                    f.write_str(" pv")?;
+                    for &mv in &line.pv {
+                        write!(f, " {}", cell_name(mv))?;
+                    }
+                }
+                Ok(())
+            },
+            Reply::BestMove(Some(mv)) => write!(f, "bestmove {}", cell_name(*mv)),
+            Reply::BestMove(None) => f.write_str("bestmove none")
+        }
+    }
+}
+
+impl FromStr for Reply {
+    type Err = ParseEngineMessageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        match words.next() {
+            Some("id") => match words.next() {
+                Some("name") => Ok(Reply::Id(words.collect::<Vec<_>>().join(" "))),
+                _ => Err(ParseEngineMessageError)
+            },
+            Some("uciok") => Ok(Reply::UciOk),
+            Some("readyok") => Ok(Reply::ReadyOk),
+            Some("info") => {
+                let mut line = Line::default();
+                while let Some(name) = words.next() {
+                    match name {
+                        "depth" => line.depth = words.next().and_then(|d| d.parse().ok()).ok_or(ParseEngineMessageError)?,
+                        "score" => line.score = match words.next() {
+                            Some("win") => Some(1),
+                            Some("loss") => Some(-1),
+                            Some("draw") => Some(0),
+                            Some("unknown") => None,
+                            _ => return Err(ParseEngineMessageError)
+                        },
+                        "pv" => {
+                            line.pv = words.by_ref().map(parse_cell).collect::<Option<_>>().ok_or(ParseEngineMessageError)?;
+                        },
+                        _ => return Err(ParseEngineMessageError)
+                    }
+                }
+                Ok(Reply::Info(line))
+            },
+            Some("bestmove") => match words.next() {
+                Some("none") => Ok(Reply::BestMove(None)),
+                Some(cell) => parse_cell(cell).map(|mv| Reply::BestMove(Some(mv))).ok_or(ParseEngineMessageError),
+                None => Err(ParseEngineMessageError)
+            },
+            _ => Err(ParseEngineMessageError)
+        }
+    }
+}
+
+/// Searches the position after `record` within `go`'s limits, reporting each
+/// line found on the way, and returns the best move.
+fn think(record: &Record, go: Go, mut report: impl FnMut(&Line)) -> Option<(u8, u8)> {
+    let st = record.state_at(record.len());
+    let p = st.turn()?;
+    let moves_left = st.succs().count().div_ceil(2) as u32;
+    let limits = go.limits(p, moves_left);
+    if limits == Limits::default() {
+        let (score, pos) = maximize(st, p);
+        report(&Line { depth: st.succs().count() as u8, score: Some(score), pv: pos.into_iter().collect() });
+        return pos
+    }
+    deepen(st, p, limits, report).pv.first().copied()
+}
+
+/// Plays the built-in AI as an engine, reading commands from `input` and
+/// answering on `output` until `quit` or the end of input. Each search runs
+/// to its limits; there is no `stop`. A `position` that doesn't parse leaves
+/// no position at all, so that the next `go` answers `bestmove none` rather
+/// than searching a board the GUI has left behind. So does a `go` that
+/// doesn't parse, rather than leave the GUI waiting.
+pub fn serve(input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    let mut record = Some(Record::default());
+    for line in input.lines() {
+        let line = line?;
+        let command = match line.parse() {
+            Ok(command) => command,
+            Err(ParseEngineMessageError) => {
+                match line.split_whitespace().next() {
+                    Some("position") => record = None,
+                    Some("go") => {
+                        writeln!(output, "{}", Reply::BestMove(None))?;
+                        output.flush()?;
+                    },
+                    _ => ()
+                }
+                continue
+            }
+        };
+        match command {
+            Command::Uci => {
+                writeln!(output, "{}", Reply::Id(NAME.to_owned()))?;
+                writeln!(output, "{}", Reply::UciOk)?;
+            },
+            Command::IsReady => writeln!(output, "{}", Reply::ReadyOk)?,
+            Command::NewGame => record = Some(Record::default()),
+            Command::Position(r) => record = Some(r),
+            Command::Go(go) => {
+                let mut res = Ok(());
+                let best = record.as_ref().and_then(|record| think(record, go, |line| {
+                    if res.is_ok() {
+                        res = writeln!(output, "{}", Reply::Info(line.clone()));
+                    }
+                }));
+                res?;
+                writeln!(output, "{}", Reply::BestMove(best))?;
+            },
+            Command::Quit => break
+        }
+        output.flush()?;
+    }
+    Ok(())
+}
+
+/// An engine running in another process.
+pub struct External {
+    name: String,
+    child: Child,
+    input: ChildStdin,
+    /// Whatever the engine says that is a reply, read on a thread of its
+    /// own so that waiting for it can time out.
+    replies: mpsc::Receiver<Reply>
+}
+
+impl External {
+    /// Starts `program` and waits for it to introduce itself.
+    pub fn spawn(program: &str, args: &[String]) -> io::Result<Self> {
+        let mut child = process::Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let input = child.stdin.take().unwrap();
+        let output = BufReader::new(child.stdout.take().unwrap());
+        // Bounded, so that an engine that won't stop talking waits for us.
+        let (tx, replies) = mpsc::sync_channel(16);
+        thread::spawn(move || {
+            for line in output.lines() {
+                let Ok(line) = line else { break };
+                if let Ok(reply) = line.parse() && tx.send(reply).is_err() {
+                    break
+                }
+            }
+        });
+        let mut engine = Self { name: program.to_owned(), child, input, replies };
+
+        engine.send(&Command::Uci)?;
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        loop {
+            match engine.recv(Some(deadline))? {
+                Reply::Id(name) => engine.name = name,
+                Reply::UciOk => break,
+                _ => ()
+            }
+        }
+        Ok(engine)
+    }
+
+    /// What the engine calls itself, or else the program it was started
+    /// from.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Tells the engine to forget the last game, and waits until it has.
+    pub fn new_game(&mut self) -> io::Result<()> {
+        self.send(&Command::NewGame)?;
+        self.send(&Command::IsReady)?;
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        while self.recv(Some(deadline))? != Reply::ReadyOk {}
+        Ok(())
+    }
+
+    /// Asks for a move in the position after `record`. Returns it along with
+    /// the last line the engine reported, if any. Only waits as long as it
+    /// takes if `go` has no `movetime`.
+    pub fn go(&mut self, record: &Record, go: Go) -> io::Result<(Option<(u8, u8)>, Option<Line>)> {
+        self.send(&Command::Position(record.clone()))?;
+        self.send(&Command::Go(go))?;
+        let deadline = go.movetime.map(|t| Instant::now() + t + MOVE_GRACE);
+        let mut last = None;
+        loop {
+            match self.recv(deadline)? {
+                Reply::Info(line) => last = Some(line),
+                Reply::BestMove(mv) => return Ok((mv, last)),
+                _ => ()
+            }
+        }
+    }
+
+    fn send(&mut self, command: &Command) -> io::Result<()> {
+        writeln!(self.input, "{command}")?;
+        self.input.flush()
+    }
+
+    /// Gives up on an engine that hasn't replied by `deadline`, since
+    /// whatever it said later would be taken for the answer to something
+    /// else.
+    fn recv(&mut self, deadline: Option<Instant>) -> io::Result<Reply> {
+        let reply = match deadline {
+            Some(deadline) => self.replies.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+            None => self.replies.recv().map_err(RecvTimeoutError::from)
+        };
+        match reply {
+            Ok(reply) => Ok(reply),
+            Err(RecvTimeoutError::Timeout) => {
+                let _ = self.child.kill();
+                Err(io::Error::new(io::ErrorKind::TimedOut, format!("{} didn't answer in time", self.name)))
+            },
+            Err(RecvTimeoutError::Disconnected) => Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("{} exited", self.name)))
+        }
+    }
+}
+
+/// Doesn't wait for the engine to finish thinking.
+impl Drop for External {
+    fn drop(&mut self) {
+        let _ = self.send(&Command::Quit);
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ten moves in with nobody through, and X to move.
+    const MIDGAME: &str = "a1 b1 c1 d1 a2 b2 c2 d2 b3 a3";
+
+    #[test]
+    fn messages_round_trip() {
+        let go = Go { depth: Some(3), movetime: None, time: [Some(Duration::from_secs(5)), None], inc: [Duration::ZERO, Duration::from_secs(1)] };
+        for command in [Command::Uci, Command::Position(Record::default()), Command::Position(MIDGAME.parse().unwrap()), Command::Go(go), Command::Quit] {
+            assert_eq!(command.to_string().parse(), Ok(command));
+        }
+        let line = Line { depth: 2, score: Some(-1), pv: vec![(2, 2), (3, 2)] };
+        for reply in [Reply::Id("some engine".to_owned()), Reply::Info(line), Reply::Info(Line::default()), Reply::BestMove(Some((0, 3))), Reply::BestMove(None)] {
+            assert_eq!(reply.to_string().parse(), Ok(reply));
+        }
+        assert_eq!("go movetime 500".parse(), Ok(Command::Go(Go { movetime: Some(Duration::from_millis(500)), ..Go::default() })));
+        // Unknown parameters are skipped, leaving the rest.
+        assert_eq!("go infinite nodes 5 depth 2".parse(), Ok(Command::Go(Go { depth: Some(2), ..Go::default() })));
+        for bad in ["", "position", "position startpos moves a1 a1", "go depth", "go movetime", "frobnicate"] {
+            assert_eq!(bad.parse::<Command>(), Err(ParseEngineMessageError), "{bad}");
+        }
+    }
+
+    #[test]
+    fn clock_time_is_spread_over_the_moves_left() {
+        let go = Go { time: [Some(Duration::from_secs(10)), Some(Duration::from_secs(1))], inc: [Duration::from_secs(1); 2], ..Go::default() };
+        assert_eq!(go.limits(Player::X, 5).time, Some(Duration::from_secs(3)));
+        assert_eq!(go.limits(Player::O, 5).time, Some(Duration::from_millis(500)));
+        assert_eq!(Go { movetime: Some(Duration::from_secs(2)), ..go }.limits(Player::X, 5).time, Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn serves_a_search() {
+        let input = format!("uci\nisready\nposition startpos moves {MIDGAME}\ngo depth 3\nquit\ngo\n");
+        let mut output = Vec::new();
+        serve(input.as_bytes(), &mut output).unwrap();
+
+        let replies = String::from_utf8(output).unwrap().lines().map(|l| l.parse().unwrap()).collect::<Vec<Reply>>();
+        assert_eq!(replies[..3], [Reply::Id(NAME.to_owned()), Reply::UciOk, Reply::ReadyOk]);
+        let [Reply::Info(first), .., Reply::Info(last), Reply::BestMove(Some(best))] = &replies[3..] else { panic!("{replies:?}") };
+        assert_eq!(first.depth, 1);
+        assert!(last.depth <= 3 && last.pv[0] == *best);
+        assert!(MIDGAME.parse::<Record>().unwrap().state_at(10).do_move(best.0, best.1).is_ok());
+    }
+
+    #[test]
+    fn bad_positions_and_searches_get_no_move() {
+        let input = format!("position startpos moves {MIDGAME}\nposition startpos moves a1 a1\ngo depth 1\nposition startpos\ngo depth x\ngo depth 1\n");
+        let mut output = Vec::new();
+        serve(input.as_bytes(), &mut output).unwrap();
+
+        let replies = String::from_utf8(output).unwrap().lines().map(|l| l.parse().unwrap()).collect::<Vec<Reply>>();
+        assert_eq!(replies[..2], [Reply::BestMove(None), Reply::BestMove(None)]);
+        assert!(matches!(replies[2..], [Reply::Info(_), Reply::BestMove(Some(_))]), "{replies:?}");
+    }
+
+    #[test]
+    fn engines_that_go_quiet_time_out() {
+        let sh = |script: &str| External::spawn("sh", &["-c".to_owned(), script.to_owned()]);
+        for script in ["exec sleep 10", "exec yes info depth 1 score unknown pv a1"] {
+            let start = Instant::now();
+            assert_eq!(sh(script).err().map(|e| e.kind()), Some(io::ErrorKind::TimedOut), "{script}");
+            assert!(start.elapsed() < 2 * HANDSHAKE_TIMEOUT);
+        }
+
+        let mut engine = sh("read line; echo uciok; exec sleep 10").unwrap();
+        let start = Instant::now();
+        let go = Go { movetime: Some(Duration::from_millis(100)), ..Go::default() };
+        assert_eq!(engine.go(&Record::default(), go).err().map(|e| e.kind()), Some(io::ErrorKind::TimedOut));
+        assert!(start.elapsed() < 2 * MOVE_GRACE);
+    }
+}
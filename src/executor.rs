@@ -0,0 +1,262 @@
+//! A single-threaded executor for tasks that report back to a context, such
+//! as the app. Tasks run wherever `schedule` sends their runnables, and their
+//! callbacks queue up until the owner of the context applies them.
+//!
+//! Cancelling a task also discards its callback if that is already queued,
+//! so nothing a cancelled task produced ever reaches the context.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    pin::Pin,
+    rc::Rc,
+    sync::Arc,
+    task::{Context, Poll}
+};
+
+use async_task::{Runnable, Task};
+
+type Completion<C> = Box<dyn FnOnce(&mut C)>;
+
+pub struct Executor<C> {
+    schedule: Arc<dyn Fn(Runnable) + Send + Sync>,
+    completions: Rc<RefCell<VecDeque<Completion<C>>>>
+}
+
+/// Clones share the same queue of callbacks.
+impl<C> Clone for Executor<C> {
+    fn clone(&self) -> Self {
+        Self { schedule: self.schedule.clone(), completions: self.completions.clone() }
+    }
+}
+
+impl<C: 'static> Executor<C> {
+    pub fn new(schedule: impl Fn(Runnable) + Send + Sync + 'static) -> Self {
+        Self {
+            schedule: Arc::new(schedule),
+            completions: Rc::default()
+        }
+    }
+
+    pub fn spawn<T: 'static>(&self, fut: impl Future<Output = T> + 'static) -> JoinHandle<T> {
+        let schedule = self.schedule.clone();
+        let (r, task) = async_task::spawn_local(fut, move |r| schedule(r));
+        r.schedule();
+        JoinHandle { task: Some(task), cancelled: Rc::default() }
+    }
+
+    /// Spawns `fut` and queues `cb` with its output once it finishes.
+    pub fn spawn_cb<T: 'static>(&self, fut: impl Future<Output = T> + 'static, cb: impl FnOnce(&mut C, T) + 'static) -> JoinHandle<()> {
+        let completions = self.completions.clone();
+        let cancelled = Rc::new(Cell::new(false));
+        let mut handle = self.spawn({
+            let cancelled = cancelled.clone();
+            async move {
+                let res = fut.await;
+                completions.borrow_mut().push_back(Box::new(move |ctx: &mut C| {
+                    if !cancelled.get() {
+                        cb(ctx, res);
+                    }
+                }));
+            }
+        });
+        handle.cancelled = cancelled;
+        handle
+    }
+
+    /// Like [`Self::spawn_cb`], but the task belongs to `scope` and is
+    /// cancelled when it is reset.
+    pub fn spawn_scoped<T: 'static>(&self, scope: &Scope, fut: impl Future<Output = T> + 'static, cb: impl FnOnce(&mut C, T) + 'static) {
+        scope.hold(self.spawn_cb(fut, cb));
+    }
+
+    /// Takes the oldest queued callback. Callbacks may spawn more tasks, so
+    /// the owner applies them one at a time until none are left.
+    pub fn next_completion(&self) -> Option<Completion<C>> {
+        self.completions.borrow_mut().pop_front()
+    }
+}
+
+/// A spawned task's result. Dropping the handle cancels the task unless it
+/// was detached first.
+pub struct JoinHandle<T> {
+    task: Option<Task<T>>,
+    /// Shared with the task's queued callback, if it has one.
+    cancelled: Rc<Cell<bool>>
+}
+
+impl<T> JoinHandle<T> {
+    /// Lets the task run to completion on its own.
+    pub fn detach(mut self) {
+        self.task.take().unwrap().detach();
+    }
+
+    /// Whether the task and any callback it queued are done with, one way or
+    /// the other. Only the handle is left holding the flag by then.
+    fn is_settled(&self) -> bool {
+        self.task.as_ref().is_none_or(|t| t.is_finished()) && Rc::strong_count(&self.cancelled) == 1
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        Pin::new(self.task.as_mut().unwrap()).poll(cx)
+    }
+}
+
+impl<T> Drop for JoinHandle<T> {
+    fn drop(&mut self) {
+        if self.task.take().is_some() {
+            self.cancelled.set(true);
+        }
+    }
+}
+
+/// Tasks that are cancelled together, such as those working on one game.
+#[derive(Default)]
+pub struct Scope {
+    handles: RefCell<Vec<JoinHandle<()>>>
+}
+
+impl Scope {
+    fn hold(&self, handle: JoinHandle<()>) {
+        let mut handles = self.handles.borrow_mut();
+        handles.retain(|h| !h.is_settled());
+        handles.push(handle);
+    }
+
+    /// Cancels every task in the scope. Their callbacks never run, even if
+    /// already queued.
+    pub fn reset(&self) {
+        self.handles.borrow_mut().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, sync::mpsc};
+
+    use super::*;
+
+    fn executor<C: 'static>() -> (Executor<C>, mpsc::Receiver<Runnable>) {
+        let (tx, rx) = mpsc::channel();
+        (Executor::new(move |r| tx.send(r).unwrap()), rx)
+    }
+
+    /// Runs everything scheduled, applying callbacks as the app would.
+    fn pump<C: 'static>(exec: &Executor<C>, rx: &mpsc::Receiver<Runnable>, ctx: &mut C) {
+        while let Ok(r) = rx.try_recv() {
+            r.run();
+            while let Some(cb) = exec.next_completion() {
+                cb(ctx);
+            }
+        }
+    }
+
+    #[test]
+    fn callbacks_finishing_together_all_apply() {
+        let (exec, rx) = executor::<Vec<u32>>();
+        for i in 0..3 {
+            exec.spawn_cb(async move { i }, |v, i| v.push(i)).detach();
+        }
+
+        // Run every task before applying any callback.
+        let runnables: Vec<_> = rx.try_iter().collect();
+        for r in runnables {
+            r.run();
+        }
+        let mut v = Vec::new();
+        while let Some(cb) = exec.next_completion() {
+            cb(&mut v);
+        }
+        assert_eq!(v, [0, 1, 2]);
+    }
+
+    #[test]
+    fn callback_can_spawn() {
+        struct Ctx {
+            exec: Rc<Executor<Ctx>>,
+            log: Vec<&'static str>
+        }
+
+        let (exec, rx) = executor::<Ctx>();
+        let exec = Rc::new(exec);
+        let mut ctx = Ctx { exec: exec.clone(), log: Vec::new() };
+
+        exec.spawn_cb(async {}, |ctx, ()| {
+            ctx.log.push("first");
+            ctx.exec.spawn_cb(async {}, |ctx, ()| ctx.log.push("second")).detach();
+        }).detach();
+
+        pump(&exec, &rx, &mut ctx);
+        assert_eq!(ctx.log, ["first", "second"]);
+    }
+
+    #[test]
+    fn tasks_can_await_each_other() {
+        let (exec, rx) = executor::<Vec<u32>>();
+        let a = exec.spawn(async { 20 });
+        let b = exec.spawn(async move { a.await + 1 });
+        exec.spawn_cb(async move { b.await * 2 }, |v, n| v.push(n)).detach();
+
+        let mut v = Vec::new();
+        pump(&exec, &rx, &mut v);
+        assert_eq!(v, [42]);
+    }
+
+    #[test]
+    fn cancelling_discards_a_queued_callback() {
+        let (exec, rx) = executor::<Vec<u32>>();
+        let handle = exec.spawn_cb(async { 1 }, |v, n| v.push(n));
+        for r in rx.try_iter() {
+            r.run();
+        }
+        drop(handle);
+
+        let mut v = Vec::new();
+        while let Some(cb) = exec.next_completion() {
+            cb(&mut v);
+        }
+        assert!(v.is_empty());
+    }
+
+    #[test]
+    fn resetting_a_scope_cancels_its_tasks() {
+        let (exec, rx) = executor::<Vec<u32>>();
+        let scope = Scope::default();
+        exec.spawn_scoped(&scope, async { 1 }, |v, n| v.push(n));
+        exec.spawn_scoped(&scope, std::future::pending::<u32>(), |v, n| v.push(n));
+        exec.spawn_cb(async { 2 }, |v, n| v.push(n)).detach();
+
+        // The first task finishes before the reset, but its callback is
+        // still waiting to be applied.
+        let runnables: Vec<_> = rx.try_iter().collect();
+        for r in runnables {
+            r.run();
+        }
+        scope.reset();
+        exec.spawn_scoped(&scope, async { 3 }, |v, n| v.push(n));
+
+        let mut v = Vec::new();
+        pump(&exec, &rx, &mut v);
+        assert_eq!(v, [2, 3]);
+    }
+
+    #[test]
+    fn dropping_the_handle_cancels() {
+        let (exec, rx) = executor::<Vec<u32>>();
+        let ran = Rc::new(Cell::new(false));
+        let handle = exec.spawn_cb({
+            let ran = ran.clone();
+            async move { ran.set(true) }
+        }, |v, ()| v.push(1));
+        drop(handle);
+
+        let mut v = Vec::new();
+        pump(&exec, &rx, &mut v);
+        assert!(!ran.get());
+        assert!(v.is_empty());
+    }
+}
@@ -0,0 +1,70 @@
+use std::{io, time::Duration};
+
+use anyhow::Context as _;
+
+use crate::{
+    game::Record,
+    rend::{Layout, Renderer, Scene, Svg}
+};
+
+/// How long the final position stays up before the animation loops.
+const HOLD_FRAMES: u32 = 3;
+
+fn frame(record: &Record, ply: usize) -> Scene {
+    Scene {
+        last_move: record.last_move(ply),
+        ..record.state_at(ply).into()
+    }.with_moves(&record.moves()[..ply])
+}
+
+/// Writes the game as a looping GIF with one frame per position, starting
+/// from the empty board.
+pub fn gif(rend: &mut Renderer, record: &Record, (w, h): (u32, u32), delay: Duration, out: impl io::Write) -> anyhow::Result<()> {
+    let (gw, gh) = (u16::try_from(w).context("width too large for a GIF")?, u16::try_from(h).context("height too large for a GIF")?);
+    let mut encoder = gif::Encoder::new(out, gw, gh, &[])?;
+    encoder.set_repeat(gif::Repeat::Infinite)?;
+
+    // GIF delays are in hundredths of a second.
+    let delay = u16::try_from(delay.as_millis() / 10).unwrap_or(u16::MAX);
+
+    for ply in 0..=record.len() {
+        let pixmap = rend.render_offscreen(&frame(record, ply), w, h).context("size must be non-zero")?;
+        let mut rgba = pixmap.pixels().iter()
+            .flat_map(|p| {
+                let c = p.demultiply();
+                [c.red(), c.green(), c.blue(), c.alpha()]
+            })
+            .collect::<Vec<_>>();
+
+        let mut frame = gif::Frame::from_rgba_speed(gw, gh, &mut rgba, 10);
+        frame.delay = if ply == record.len() { delay.saturating_mul(HOLD_FRAMES as u16) } else { delay };
+        encoder.write_frame(&frame)?;
+    }
+
+    Ok(())
+}
+
+/// Builds an SVG that steps through the game with SMIL animation. Each
+/// position is a group shown for `delay`, and the last one stays up.
+pub fn svg(rend: &mut Renderer, record: &Record, delay: Duration) -> String {
+    let secs = delay.as_secs_f32();
+    rend.set_layout(Layout::default());
+    let mut svg = Svg::default();
+
+    for ply in 0..=record.len() {
+        let visibility = if ply == 0 { "visible" } else { "hidden" };
+        svg.raw(&format!("<g visibility=\"{visibility}\">\n"));
+        if ply > 0 {
+            svg.raw(&format!("<set attributeName=\"visibility\" to=\"visible\" begin=\"{}s\"/>\n", ply as f32 * secs));
+        }
+        if ply < record.len() {
+            svg.raw(&format!("<set attributeName=\"visibility\" to=\"hidden\" begin=\"{}s\"/>\n", (ply + 1) as f32 * secs));
+        }
+
+        rend.prepare(&frame(record, ply));
+        rend.render_svg_regions(&mut svg);
+        svg.raw("</g>\n");
+    }
+
+    svg.document(rend.layout().extent(), rend.theme().letterbox)
+}
@@ -0,0 +1,113 @@
+//! A built-in stroke font. Glyphs are polylines on a grid 4 units wide and
+//! 6 tall, with the origin at the top left and the baseline at y = 6. A
+//! polyline with a single point is a dot.
+
+use tiny_skia::PathBuilder;
+
+type Glyph = &'static [&'static [(u8, u8)]];
+
+const GLYPH_WIDTH: f32 = 4.;
+const GLYPH_HEIGHT: f32 = 6.;
+const ADVANCE: f32 = 5.5;
+
+const RING: &[(u8, u8)] = &[(1, 0), (3, 0), (4, 1), (4, 5), (3, 6), (1, 6), (0, 5), (0, 1), (1, 0)];
+const BOWL: &[(u8, u8)] = &[(0, 6), (0, 0), (3, 0), (4, 1), (4, 2), (3, 3), (0, 3)];
+
+fn glyph(c: char) -> Glyph {
+    match c.to_ascii_uppercase() {
+        'A' => &[&[(0, 6), (0, 2), (2, 0), (4, 2), (4, 6)], &[(0, 4), (4, 4)]],
+        'B' => &[&[(0, 3), (3, 3), (4, 4), (4, 5), (3, 6), (0, 6), (0, 0), (3, 0), (4, 1), (4, 2), (3, 3)]],
+        'C' => &[&[(4, 1), (3, 0), (1, 0), (0, 1), (0, 5), (1, 6), (3, 6), (4, 5)]],
+        'D' => &[&[(0, 0), (0, 6), (2, 6), (4, 4), (4, 2), (2, 0), (0, 0)]],
+        'E' => &[&[(4, 0), (0, 0), (0, 6), (4, 6)], &[(0, 3), (3, 3)]],
+        'F' => &[&[(4, 0), (0, 0), (0, 6)], &[(0, 3), (3, 3)]],
+        'G' => &[&[(4, 1), (3, 0), (1, 0), (0, 1), (0, 5), (1, 6), (3, 6), (4, 5), (4, 3), (2, 3)]],
+        'H' => &[&[(0, 0), (0, 6)], &[(4, 0), (4, 6)], &[(0, 3), (4, 3)]],
+        'I' => &[&[(1, 0), (3, 0)], &[(2, 0), (2, 6)], &[(1, 6), (3, 6)]],
+        'J' => &[&[(4, 0), (4, 5), (3, 6), (1, 6), (0, 5)]],
+        'K' => &[&[(0, 0), (0, 6)], &[(4, 0), (0, 3), (4, 6)]],
+        'L' => &[&[(0, 0), (0, 6), (4, 6)]],
+        'M' => &[&[(0, 6), (0, 0), (2, 3), (4, 0), (4, 6)]],
+        'N' => &[&[(0, 6), (0, 0), (4, 6), (4, 0)]],
+        'O' => &[RING],
+        'P' => &[BOWL],
+        'Q' => &[RING, &[(2, 4), (4, 6)]],
+        'R' => &[BOWL, &[(2, 3), (4, 6)]],
+        'S' => &[&[(4, 1), (3, 0), (1, 0), (0, 1), (0, 2), (1, 3), (3, 3), (4, 4), (4, 5), (3, 6), (1, 6), (0, 5)]],
+        'T' => &[&[(0, 0), (4, 0)], &[(2, 0), (2, 6)]],
+        'U' => &[&[(0, 0), (0, 5), (1, 6), (3, 6), (4, 5), (4, 0)]],
+        'V' => &[&[(0, 0), (2, 6), (4, 0)]],
+        'W' => &[&[(0, 0), (1, 6), (2, 3), (3, 6), (4, 0)]],
+        'X' => &[&[(0, 0), (4, 6)], &[(4, 0), (0, 6)]],
+        'Y' => &[&[(0, 0), (2, 3), (4, 0)], &[(2, 3), (2, 6)]],
+        'Z' => &[&[(0, 0), (4, 0), (0, 6), (4, 6)]],
+        '0' => &[RING, &[(4, 1), (0, 5)]],
+        '1' => &[&[(1, 1), (2, 0), (2, 6)], &[(1, 6), (3, 6)]],
+        '2' => &[&[(0, 1), (1, 0), (3, 0), (4, 1), (4, 2), (0, 6), (4, 6)]],
+        '3' => &[&[(0, 1), (1, 0), (3, 0), (4, 1), (4, 2), (3, 3), (4, 4), (4, 5), (3, 6), (1, 6), (0, 5)], &[(1, 3), (3, 3)]],
+        '4' => &[&[(3, 6), (3, 0), (0, 4), (4, 4)]],
+        '5' => &[&[(4, 0), (0, 0), (0, 3), (3, 3), (4, 4), (4, 5), (3, 6), (0, 6)]],
+        '6' => &[&[(3, 0), (1, 0), (0, 1), (0, 5), (1, 6), (3, 6), (4, 5), (4, 4), (3, 3), (0, 3)]],
+        '7' => &[&[(0, 0), (4, 0), (1, 6)]],
+        '8' => &[&[(1, 3), (0, 2), (0, 1), (1, 0), (3, 0), (4, 1), (4, 2), (3, 3), (1, 3), (0, 4), (0, 5), (1, 6), (3, 6), (4, 5), (4, 4), (3, 3)]],
+        '9' => &[&[(4, 3), (1, 3), (0, 2), (0, 1), (1, 0), (3, 0), (4, 1), (4, 5), (3, 6), (1, 6)]],
+        ' ' => &[],
+        ':' => &[&[(2, 2)], &[(2, 5)]],
+        '.' => &[&[(2, 6)]],
+        ',' => &[&[(2, 5), (1, 7)]],
+        '-' => &[&[(1, 3), (3, 3)]],
+        '+' => &[&[(1, 3), (3, 3)], &[(2, 2), (2, 4)]],
+        '/' => &[&[(4, 0), (0, 6)]],
+        '(' => &[&[(3, 0), (1, 2), (1, 4), (3, 6)]],
+        ')' => &[&[(1, 0), (3, 2), (3, 4), (1, 6)]],
+        '!' => &[&[(2, 0), (2, 4)], &[(2, 6)]],
+        _ => &[&[(0, 1), (1, 0), (3, 0), (4, 1), (4, 2), (2, 3), (2, 4)], &[(2, 6)]]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right
+}
+
+/// The width of `s` set at cap height `size`.
+pub fn width(s: &str, size: f32) -> f32 {
+    let n = s.chars().count();
+    if n == 0 {
+        return 0.
+    }
+    let unit = size / GLYPH_HEIGHT;
+    ((n - 1) as f32 * ADVANCE + GLYPH_WIDTH) * unit
+}
+
+/// Adds the outline strokes of `s` to `builder`. `(x, y)` is the baseline
+/// point picked out by `align`, and `size` is the cap height.
+pub fn text(builder: &mut PathBuilder, s: &str, x: f32, y: f32, size: f32, align: Align) {
+    let unit = size / GLYPH_HEIGHT;
+    let x = match align {
+        Align::Left => x,
+        Align::Center => x - width(s, size) / 2.,
+        Align::Right => x - width(s, size)
+    };
+    let top = y - size;
+
+    for (i, c) in s.chars().enumerate() {
+        let left = x + i as f32 * ADVANCE * unit;
+        let pt = |(gx, gy): (u8, u8)| (left + gx as f32 * unit, top + gy as f32 * unit);
+
+        for &line in glyph(c) {
+            let (sx, sy) = pt(line[0]);
+            builder.move_to(sx, sy);
+            if line.len() == 1 {
+                // A zero-length segment with a round cap leaves a dot.
+                builder.line_to(sx, sy);
+            }
+            for &p in &line[1..] {
+                let (px, py) = pt(p);
+                builder.line_to(px, py);
+            }
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::{error::Error, fmt::Display};
+use std::{error::Error, fmt::Display, str::FromStr};
 
 use rayon::iter::{IndexedParallelIterator as _, IntoParallelIterator as _, ParallelIterator};
 
@@ -25,7 +25,25 @@ pub enum Score {
     Tie
 }
 
-#[derive(Debug, Clone, Copy, Default)]
+/// Results across a session of games.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Tally {
+    pub x: u32,
+    pub o: u32,
+    pub ties: u32
+}
+
+impl Tally {
+    pub fn record(&mut self, score: Score) {
+        match score {
+            Score::Win(Player::X) => self.x += 1,
+            Score::Win(Player::O) => self.o += 1,
+            Score::Tie => self.ties += 1
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct State {
     board: [Option<Player>; (N*N) as usize],
     score: Option<Score>
@@ -42,6 +60,28 @@ impl Display for InvalidMove {
 
 impl Error for InvalidMove {}
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseStateError;
+
+impl Display for ParseStateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("invalid position")
+    }
+}
+
+impl Error for ParseStateError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseRecordError;
+
+impl Display for ParseRecordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("invalid move list")
+    }
+}
+
+impl Error for ParseRecordError {}
+
 impl State {
     pub fn score(self) -> Option<Score> {
         self.score
@@ -135,6 +175,23 @@ impl State {
         None
     }
 
+    /// The end cells of the winning line, if the game has been won.
+    pub fn win_line(self) -> Option<((u8, u8), (u8, u8))> {
+        let Some(Score::Win(p)) = self.score else { return None };
+
+        let line = lines().find(|&line| cells(line).all(|idx| self.board[idx] == Some(p))).unwrap();
+        let ((x0, y0), (dx, dy)) = line;
+        let n = N as i8;
+        let (x1, y1) = (x0 + (n - 1) * dx, y0 + (n - 1) * dy);
+        Some(((x0 as u8, y0 as u8), (x1 as u8, y1 as u8)))
+    }
+
+    /// How many lines `p` could still complete, having none of the other
+    /// player's marks.
+    pub fn open_lines(self, p: Player) -> usize {
+        lines().filter(|&line| cells(line).all(|idx| self.board[idx] != Some(p.other()))).count()
+    }
+
     pub fn board(self) -> [Option<Player>; (N*N) as usize] {
         self.board
     }
@@ -203,4 +260,158 @@ impl State {
                 v.is_none().then_some(((i % N) as u8, (i / N) as u8))
             })
     }
-}
\ No newline at end of file
+}
+
+/// Positions are written as rows from top to bottom separated by `/`, with
+/// `x`, `o` and `.` for the cells, e.g. `x.../.o../..../....`.
+impl Display for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, cell) in self.board.iter().enumerate() {
+            if i > 0 && (i as u32).is_multiple_of(N) {
+                f.write_str("/")?;
+            }
+            f.write_str(match cell {
+                Some(Player::X) => "x",
+                Some(Player::O) => "o",
+                None => "."
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for State {
+    type Err = ParseStateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut st = State::default();
+
+        let rows = s.trim().split('/').collect::<Vec<_>>();
+        if rows.len() != N as usize {
+            return Err(ParseStateError);
+        }
+
+        for (y, row) in rows.into_iter().enumerate() {
+            if row.chars().count() != N as usize {
+                return Err(ParseStateError);
+            }
+            for (x, c) in row.chars().enumerate() {
+                st.board[x + y * N as usize] = match c.to_ascii_lowercase() {
+                    'x' => Some(Player::X),
+                    'o' => Some(Player::O),
+                    '.' => None,
+                    _ => return Err(ParseStateError)
+                };
+            }
+        }
+
+        let count = |p| st.board.iter().filter(|&&c| c == Some(p)).count();
+        let (x, o) = (count(Player::X), count(Player::O));
+        if x != o && x != o + 1 {
+            return Err(ParseStateError);
+        }
+
+        // The winner must have made the last move.
+        st.score = st.check_win();
+        match st.score {
+            Some(Score::Win(Player::X)) if x != o + 1 => Err(ParseStateError),
+            Some(Score::Win(Player::O)) if x != o => Err(ParseStateError),
+            _ => Ok(st)
+        }
+    }
+}
+
+/// Every row, column and diagonal, as its first cell and the step to the
+/// next.
+fn lines() -> impl Iterator<Item = ((i8, i8), (i8, i8))> {
+    let n = N as i8;
+    (0..n).map(|y| ((0, y), (1, 0)))
+        .chain((0..n).map(|x| ((x, 0), (0, 1))))
+        .chain([((0, 0), (1, 1)), ((n - 1, 0), (-1, 1))])
+}
+
+/// The board indices along a line from [`lines`].
+fn cells(((x0, y0), (dx, dy)): ((i8, i8), (i8, i8))) -> impl Iterator<Item = usize> {
+    let n = N as i8;
+    (0..n).map(move |k| ((x0 + k * dx) + (y0 + k * dy) * n) as usize)
+}
+
+/// Writes a cell as a column letter and a row number counted from the top,
+/// matching the board labels, e.g. `b2`.
+pub fn cell_name((x, y): (u8, u8)) -> String {
+    format!("{}{}", (b'a' + x) as char, y + 1)
+}
+
+/// Reads a cell written by [`cell_name`], in either case. Doesn't check that
+/// it is on the board.
+pub fn parse_cell(s: &str) -> Option<(u8, u8)> {
+    let mut chars = s.chars();
+    let col = chars.next()?.to_ascii_lowercase();
+    let row = chars.as_str().parse::<u8>().ok()?;
+    if !col.is_ascii_lowercase() || row == 0 {
+        return None
+    }
+    Some((col as u8 - b'a', row - 1))
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Record {
+    moves: Vec<(u8, u8)>
+}
+
+impl Record {
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+
+    pub fn moves(&self) -> &[(u8, u8)] {
+        &self.moves
+    }
+
+    pub fn push(&mut self, x: u8, y: u8) {
+        self.moves.push((x, y));
+    }
+
+    pub fn last_move(&self, ply: usize) -> Option<(u8, u8)> {
+        ply.checked_sub(1).map(|i| self.moves[i])
+    }
+
+    pub fn state_at(&self, ply: usize) -> State {
+        self.moves[..ply].iter()
+            .fold(State::default(), |st, &(x, y)| st.do_move(x, y).unwrap())
+    }
+}
+
+/// Moves are written as their cells, e.g. `b2 a1 c3`.
+impl Display for Record {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, &mv) in self.moves.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            f.write_str(&cell_name(mv))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Record {
+    type Err = ParseRecordError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut record = Record::default();
+        let mut st = State::default();
+
+        for mv in s.split(|c: char| c.is_whitespace() || c == ',').filter(|m| !m.is_empty()) {
+            let (x, y) = parse_cell(mv).ok_or(ParseRecordError)?;
+            st = st.do_move(x, y).map_err(|_| ParseRecordError)?;
+            record.push(x, y);
+        }
+
+        Ok(record)
+    }
+}
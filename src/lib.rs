@@ -0,0 +1,10 @@
+//! The rules, the AI and the engine protocol, shared by the game, the server
+//! and the arena.
+
+#![allow(clippy::type_complexity)]
+
+pub mod ai;
+pub mod engine;
+pub mod game;
+
+pub const N: u32 = 4;
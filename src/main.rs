@@ -1,38 +1,70 @@
 #![allow(clippy::type_complexity)]
 
-mod ai;
-mod game;
+mod cli;
+mod controller;
+#[cfg(test)]
+mod driver;
+mod executor;
+mod export;
+mod font;
+mod net;
 mod rend;
+mod replay;
+mod theme;
+mod time_control;
 mod timer;
 
 use std::{
-    cell::Cell,
-    collections::{binary_heap, BinaryHeap},
+    env,
+    fs,
+    io,
     iter,
     mem,
-    rc::Rc,
+    net::TcpListener,
+    sync::OnceLock,
     time::{Duration, Instant}
 };
 
+use anyhow::Context as _;
 use async_task::Runnable;
+use rayon::prelude::*;
 use softbuffer::{Context, Surface};
 use tiny_skia::*;
+use ttt::{ai, engine, game, N};
 use winit::{
     application::ApplicationHandler,
-    dpi::{PhysicalPosition, PhysicalSize},
-    event::{ElementState, MouseButton, StartCause},
+    dpi::{LogicalSize, PhysicalPosition, PhysicalSize},
+    event::{ElementState, KeyEvent, MouseButton, StartCause, Touch, TouchPhase},
     event_loop::*,
+    keyboard::{Key, NamedKey},
     window::{Window, WindowAttributes}
 };
 
 use crate::{
-    ai::maximize,
-    game::{Player, State},
-    rend::Renderer,
-    timer::{PendingTimer, Timer}
+    controller::{Event, GameController, Seat},
+    engine::External,
+    executor::{Executor, Scope},
+    game::{Player, Tally},
+    net::{NetError, Session},
+    rend::{Layout, Region, Renderer, Scene},
+    replay::Replay,
+    theme::Theme,
+    time_control::TimeControl,
+    timer::{Interval, Timers}
 };
 
-const N: u32 = 4;
+const REPLAY_INTERVAL: Duration = Duration::from_millis(800);
+
+const PLACE_DURATION: Duration = Duration::from_millis(250);
+const SWEEP_DURATION: Duration = Duration::from_millis(400);
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+/// Often enough for a clock showing tenths of a second.
+const CLOCK_INTERVAL: Duration = Duration::from_millis(100);
+
+const THEME_FILE: &str = "ttt-theme.conf";
+
+/// How far, in logical pixels, a touch may wander before it stops being a tap.
+const TAP_SLOP: f64 = 12.;
 
 async fn unblock<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
     let (prod, cons) = oneshot::channel();
@@ -40,97 +72,451 @@ async fn unblock<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T
     cons.await.unwrap()
 }
 
+fn progress(now: Instant, start: Instant, duration: Duration) -> f32 {
+    (now.saturating_duration_since(start).as_secs_f32() / duration.as_secs_f32()).min(1.)
+}
+
+/// Below this many pixels, handing rows out to threads costs more than it
+/// saves.
+const PARALLEL_BLIT_PIXELS: u32 = 64 * 1024;
+
+/// Threads for presenting only. The global pool is busy whenever the AI is
+/// thinking, and a frame mustn't wait for that.
+fn blit_pool() -> &'static rayon::ThreadPool {
+    static POOL: OnceLock<rayon::ThreadPool> = OnceLock::new();
+    POOL.get_or_init(|| {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(2)
+            .thread_name(|i| format!("blit-{i}"))
+            .build()
+            .unwrap()
+    })
+}
+
+/// Copies `rect` of `fb` into a softbuffer buffer of the same size. Large
+/// rects are split by row over [`blit_pool`].
+fn blit(buf: &mut [u32], fb: &Pixmap, rect: IntRect) {
+    let w = fb.width() as usize;
+    let (left, right) = (rect.x() as usize, rect.right() as usize);
+    let row = |(dst, src): (&mut [u32], &[PremultipliedColorU8])| {
+        for (dst, src) in iter::zip(&mut dst[left..right], &src[left..right]) {
+            let src = src.demultiply();
+            *dst = u32::from_le_bytes([src.blue(), src.green(), src.red(), 0]);
+        }
+    };
+
+    let (skip, take) = (rect.y() as usize, rect.height() as usize);
+    if rect.width() * rect.height() < PARALLEL_BLIT_PIXELS {
+        buf.chunks_mut(w).zip(fb.pixels().chunks(w)).skip(skip).take(take).for_each(row);
+    } else {
+        blit_pool().install(|| {
+            buf.par_chunks_mut(w).zip(fb.pixels().par_chunks(w)).skip(skip).take(take).for_each(row);
+        });
+    }
+}
+
+struct ActiveTouch {
+    id: u64,
+    start: PhysicalPosition<f64>,
+    dragged: bool
+}
+
 enum AsyncEvent {
     Runnable(Runnable)
 }
 
+/// Sends runnables to the event loop.
+fn schedule(pxy: EventLoopProxy<AsyncEvent>) -> impl Fn(Runnable) + Send + Sync + 'static {
+    move |r| {
+        // Leak the runnable if we can't send it.
+        // Technically not needed, but it prevents a panic.
+
+        // Destructuring the EventLoopClosed guards against it
+        // potentially implementing Drop in the future.
+        if let Err(EventLoopClosed(v)) = pxy.send_event(AsyncEvent::Runnable(r)) {
+            mem::forget(v);
+        }
+    }
+}
+
 struct App {
-    pxy: EventLoopProxy<AsyncEvent>,
-    async_cb: Rc<Cell<Option<Box<dyn FnOnce(&mut App)>>>>,
-    timers: BinaryHeap<PendingTimer>,
-    last_mouse_pos: PhysicalPosition<f64>,
-    board: State,
+    exec: Executor<App>,
+    game: GameController,
+    /// The connection to a remote opponent, while it lasts.
+    net: Option<Session>,
+    /// Why the connection ended.
+    net_error: Option<NetError>,
+    /// Whether the engine gave up on the current game.
+    engine_failed: bool,
+    /// The pending replay autoplay tick.
+    autoplay: Scope,
+    timers: Timers,
+    last_mouse_pos: Option<PhysicalPosition<f64>>,
+    scale_factor: f64,
+    tally: Tally,
+    replay: Option<Replay>,
+    cursor: Option<(u8, u8)>,
+    hover: Option<(u8, u8)>,
+    touch: Option<ActiveTouch>,
+    /// The ply whose move is being animated and when its animation began.
+    anim: Option<(usize, Instant)>,
+    /// When the next scheduled redraw is due.
+    next_frame: Option<Instant>,
     sfc: Option<softbuffer::Surface<OwnedDisplayHandle, Window>>,
     fb: Option<Pixmap>,
     mask: Option<Mask>,
-    transform: Transform,
+    layout: Layout,
     rend: Renderer
 }
 
 impl App {
-    fn new(pxy: EventLoopProxy<AsyncEvent>) -> Self {
-        let board = State::default();
-        
-        let this = Self {
-            pxy,
-            async_cb: Rc::new(Cell::new(None)),
-            timers: BinaryHeap::new(),
-            last_mouse_pos: Default::default(),
-            board,
+    fn new(pxy: EventLoopProxy<AsyncEvent>, theme: Theme, control: Option<TimeControl>, net: Option<Session>, engine: Option<External>) -> Self {
+        let timers = Timers::default();
+        let seats = match (&net, &engine) {
+            (Some(net), _) => net.seats(),
+            (None, Some(_)) => [Seat::Engine, Seat::Human],
+            (None, None) => [Seat::Ai, Seat::Human]
+        };
+
+        let mut this = Self {
+            exec: Executor::new(schedule(pxy.clone())),
+            game: GameController::new(Executor::new(schedule(pxy)), timers.clone(), seats),
+            net,
+            net_error: None,
+            engine_failed: false,
+            autoplay: Scope::default(),
+            timers,
+            last_mouse_pos: None,
+            scale_factor: 1.,
+            tally: Tally::default(),
+            replay: None,
+            cursor: None,
+            hover: None,
+            touch: None,
+            anim: None,
+            next_frame: None,
             sfc: None,
             fb: None,
             mask: None,
-            transform: Transform::identity(),
+            layout: Layout::default(),
             rend: Renderer::default()
         };
 
-        this.spawn_cb(
+        this.rend.set_theme(theme);
+        this.game.set_time_control(control);
+        this.game.set_engine(engine);
+        this.game.new_game();
+        this.receive();
+
+        this
+    }
+
+    /// Against a remote opponent, offers or accepts a rematch instead.
+    fn new_game(&mut self) {
+        match self.net {
+            Some(ref mut net) => {
+                let res = net.offer_rematch(&mut self.game);
+                self.net_result(res);
+                self.request_redraw();
+            },
+            None if self.net_error.is_none() => self.game.new_game(),
+            // The opponent is gone, so there is nobody to play.
+            None => ()
+        }
+    }
+
+    /// Applies the opponent's messages as they arrive.
+    fn receive(&mut self) {
+        let Some(ref net) = self.net else { return };
+        self.spawn_cb(net.recv(), |this, msg| {
+            let Some(ref mut net) = this.net else { return };
+            let res = msg.and_then(|msg| net.on_message(&mut this.game, msg));
+            if res.is_ok() {
+                this.receive();
+            }
+            this.net_result(res);
+            this.request_redraw();
+        });
+    }
+
+    /// Drops the connection once anything goes wrong with it.
+    fn net_result(&mut self, res: Result<(), NetError>) {
+        if let Err(e) = res {
+            self.net = None;
+            self.net_error = Some(e);
+        }
+    }
+
+    fn request_redraw(&self) {
+        self.sfc.as_ref().unwrap().window().request_redraw();
+    }
+
+    /// Shows whatever the game has reported since last time.
+    fn game_events(&mut self) {
+        while let Some(event) = self.game.next_event() {
+            if let Some(ref mut net) = self.net {
+                let res = net.on_game_event(event);
+                self.net_result(res);
+            }
+
+            match event {
+                Event::Started => {
+                    self.anim = None;
+                    self.replay = None;
+                    self.engine_failed = false;
+                    self.autoplay.reset();
+                },
+                Event::Moved { ply, .. } => self.anim = Some((ply, self.timers.now())),
+                Event::EngineFailed(_) => self.engine_failed = true,
+                Event::Evaluated(_) | Event::Flagged(_) => (),
+                Event::Over(score) => self.tally.record(score)
+            }
+            self.request_redraw();
+        }
+    }
+
+    /// Makes sure a redraw comes within `delay`, for as long as something
+    /// on screen keeps changing.
+    fn redraw_within(&mut self, delay: Duration) {
+        let at = self.timers.now() + delay;
+        if self.next_frame.is_some_and(|next| next <= at) {
+            return
+        }
+
+        self.next_frame = Some(at);
+        let timer = self.timers.at(at);
+        self.spawn_cb(timer, move |this, ()| {
+            if this.next_frame == Some(at) {
+                this.next_frame = None;
+            }
+            this.request_redraw();
+        });
+    }
+
+    fn scene(&self) -> Scene {
+        let now = self.timers.now();
+        let record = self.game.record();
+        let (ply, state) = match self.replay {
+            Some(ref replay) => (replay.ply(), record.state_at(replay.ply())),
+            None => (record.len(), self.game.state())
+        };
+        let last_move = record.last_move(ply);
+        let anim_start = self.anim.filter(|&(p, _)| p == ply).map(|(_, at)| at);
+        let live = self.replay.is_none();
+
+        Scene {
+            state,
+            last_move,
+            cursor: self.cursor.filter(|_| live),
+            preview: self.hover
+                .filter(|&(x, y)| live && self.game.awaiting_human() && state.do_move(x, y).is_ok())
+                .zip(state.turn())
+                .map(|((x, y), p)| (x, y, p)),
+            placing: anim_start.zip(last_move).map(|(at, (x, y))| (x, y, progress(now, at, PLACE_DURATION))),
+            win_line: state.win_line().map(|(from, to)| {
+                (from, to, anim_start.map_or(1., |at| progress(now, at + PLACE_DURATION, SWEEP_DURATION)))
+            }),
+            tally: self.tally,
+            replay: self.replay.as_ref().map(|r| (r.ply(), record.len())),
+            eval: self.game.eval(),
+            clocks: self.game.clocks(),
+            flagged: self.game.flagged().filter(|_| live),
+            notice: self.notice(),
+            ..Scene::default()
+        }.with_moves(record.moves())
+    }
+
+    fn notice(&self) -> Option<&'static str> {
+        match (&self.net, self.net_error) {
+            (_, Some(NetError::Disconnected)) => Some("OPPONENT LEFT"),
+            (_, Some(NetError::Desync)) => Some("OUT OF SYNC"),
+            _ if self.engine_failed => Some("ENGINE FAILED"),
+            (Some(net), None) if net.offered_by_peer() => Some("REMATCH? PRESS N"),
+            (Some(net), None) if net.offered() => Some("REMATCH OFFERED"),
+            (Some(net), None) => Some(match net.side() {
+                Player::X => "YOU ARE X",
+                Player::O => "YOU ARE O"
+            }),
+            _ => None
+        }
+    }
+
+    fn replay_advanced(&mut self, before: usize) {
+        if let Some(ref replay) = self.replay && replay.ply() > before {
+            self.anim = Some((replay.ply(), self.timers.now()));
+        }
+    }
+
+    fn toggle_replay(&mut self) {
+        self.autoplay.reset();
+        self.replay = match self.replay {
+            Some(_) => None,
+            None => Some(Replay::new(self.game.record().len(), REPLAY_INTERVAL))
+        };
+        self.request_redraw();
+    }
+
+    fn schedule_autoplay(&mut self) {
+        let Some(ref replay) = self.replay else { return };
+        let interval = self.timers.interval(replay.interval());
+        self.autoplay_tick(replay.generation(), interval);
+    }
+
+    fn autoplay_tick(&mut self, generation: u64, mut interval: Interval) {
+        self.exec.spawn_scoped(
+            &self.autoplay,
             async move {
-                let pos = unblock(move || maximize(board, Player::X)).await;
-                let (x, y) = pos.unwrap();
-                board.do_move(x, y).unwrap()
+                interval.tick().await;
+                interval
             },
-            |this, nst| {
-                this.board = nst;
-                this.sfc.as_ref().unwrap().window().request_redraw();
+            move |this, mut interval| {
+                let len = this.game.record().len();
+                let Some(ref mut replay) = this.replay else { return };
+                let before = replay.ply();
+                if replay.tick(generation, len) {
+                    interval.set_period(replay.interval());
+                    this.autoplay_tick(generation, interval);
+                }
+                this.replay_advanced(before);
+                this.request_redraw();
             }
         );
+    }
 
-        this
+    fn cell_at(&self, pos: PhysicalPosition<f64>) -> Option<(u8, u8)> {
+        let mut pt = Point { x: pos.x as f32, y: pos.y as f32 };
+        self.layout.transform(Region::Board).invert()?.map_point(&mut pt);
+
+        if !(0. ..100.).contains(&pt.x) || !(0. ..100.).contains(&pt.y) {
+            return None
+        }
+
+        let x = (pt.x * (N as f32) / 100.) as u8;
+        let y = (pt.y * (N as f32) / 100.) as u8;
+        Some((x, y))
     }
 
-    fn spawn<T: 'static>(&self, fut: impl Future<Output = T> + 'static) {
-        let pxy = self.pxy.clone();
-        let (r, t) = async_task::spawn_local(
-            fut,
-            move |r| {
-                // Leak the runnable if we can't send it.
-                // Technically not needed, but it prevents a panic.
+    fn set_hover(&mut self, hover: Option<(u8, u8)>) {
+        if self.hover != hover {
+            self.hover = hover;
+            self.request_redraw();
+        }
+    }
 
-                // Destructuring the EventLoopClosed guards against it
-                // potentially implementing Drop in the future.
-                if let Err(EventLoopClosed(v)) = pxy.send_event(AsyncEvent::Runnable(r)) {
-                    mem::forget(v);
+    fn on_touch(&mut self, Touch { phase, location, id, .. }: Touch) {
+        match phase {
+            TouchPhase::Started => {
+                // Only the first finger down counts; multi-touch is ignored.
+                if self.touch.is_none() {
+                    self.touch = Some(ActiveTouch { id, start: location, dragged: false });
                 }
+            },
+            TouchPhase::Moved => {
+                if let Some(ref mut touch) = self.touch && touch.id == id {
+                    let (dx, dy) = (location.x - touch.start.x, location.y - touch.start.y);
+                    touch.dragged |= dx.hypot(dy) > TAP_SLOP * self.scale_factor;
+                }
+            },
+            TouchPhase::Ended => {
+                if let Some(touch) = self.touch.take_if(|t| t.id == id) && !touch.dragged &&
+                    let Some((x, y)) = self.cell_at(touch.start) {
+                    self.select_cell(x, y);
+                }
+            },
+            TouchPhase::Cancelled => {
+                self.touch.take_if(|t| t.id == id);
             }
-        );
-        t.detach();
-        r.schedule();
+        }
     }
 
-    fn spawn_cb<T: 'static>(&self, fut: impl Future<Output = T> + 'static, cb: impl for<'a> FnOnce(&'a mut App, T) + 'static) {
-        let async_cb = self.async_cb.clone();
-        self.spawn(async move {
-            let res = fut.await;
-            let cb = Box::new(move |this: &mut App| {
-                cb(this, res);
-            });
-            let prev = async_cb.replace(Some(cb));
-            assert!(prev.is_none());
-        })
+    fn select_cell(&mut self, x: u8, y: u8) {
+        if self.replay.is_some() {
+            return
+        }
+
+        // Moves that aren't legal, or made while the AI is thinking, do nothing.
+        let _ = self.game.play(x, y);
+    }
+
+    fn key_pressed(&mut self, key: &Key) {
+        match key {
+            Key::Character(c) if c.eq_ignore_ascii_case("r") => self.toggle_replay(),
+            Key::Character(c) if c.eq_ignore_ascii_case("n") => self.new_game(),
+            Key::Character(c) if c.eq_ignore_ascii_case("t") => {
+                self.rend.set_theme(self.rend.theme().next_preset());
+                self.request_redraw();
+            },
+            _ if self.replay.is_some() => self.replay_key(key),
+            _ => self.play_key(key)
+        }
     }
 
-    #[expect(dead_code)]
-    fn timer_at(&mut self, instant: Instant) -> Timer {
-        let (pt, timer) = Timer::at(instant);
-        self.timers.push(pt);
-        timer
+    fn play_key(&mut self, key: &Key) {
+        const LAST: u8 = (N - 1) as u8;
+        let (x, y) = self.cursor.unwrap_or((LAST / 2, LAST / 2));
+
+        let cursor = match key {
+            // The first arrow press only reveals the cursor.
+            Key::Named(NamedKey::ArrowLeft | NamedKey::ArrowRight | NamedKey::ArrowUp | NamedKey::ArrowDown)
+                if self.cursor.is_none() => (x, y),
+            Key::Named(NamedKey::ArrowLeft) => (x.saturating_sub(1), y),
+            Key::Named(NamedKey::ArrowRight) => ((x + 1).min(LAST), y),
+            Key::Named(NamedKey::ArrowUp) => (x, y.saturating_sub(1)),
+            Key::Named(NamedKey::ArrowDown) => (x, (y + 1).min(LAST)),
+            Key::Named(NamedKey::Enter | NamedKey::Space) => {
+                if self.cursor.is_some() {
+                    self.select_cell(x, y);
+                }
+                return
+            },
+            // Typing a cell's name as labelled, e.g. `c3`, plays there: the
+            // letter moves the cursor to the column and the digit picks the row.
+            Key::Character(c) if let &[col] = c.to_ascii_lowercase().as_bytes() && (b'a'..b'a' + N as u8).contains(&col) => (col - b'a', y),
+            Key::Character(c) if let Some(row @ 1..=9) = c.parse::<u8>().ok() && row as u32 <= N => {
+                let y = row - 1;
+                self.cursor = Some((x, y));
+                self.select_cell(x, y);
+                self.request_redraw();
+                return
+            },
+            _ => return
+        };
+
+        self.cursor = Some(cursor);
+        self.request_redraw();
     }
 
-    fn timer_after(&mut self, duration: Duration) -> Timer {
-        let (pt, timer) = Timer::after(duration);
-        self.timers.push(pt);
-        timer
+    fn replay_key(&mut self, key: &Key) {
+        let len = self.game.record().len();
+        let Some(ref mut replay) = self.replay else { return };
+        let (before, generation) = (replay.ply(), replay.generation());
+        let mut autoplay = false;
+
+        match key {
+            Key::Named(NamedKey::ArrowRight) => replay.step_forward(len),
+            Key::Named(NamedKey::ArrowLeft) => replay.step_back(),
+            Key::Named(NamedKey::Home) => replay.jump_to_start(),
+            Key::Named(NamedKey::End) => replay.jump_to_end(len),
+            Key::Named(NamedKey::Space) => autoplay = replay.toggle_autoplay(len),
+            Key::Character(c) if c == "+" || c == "=" => replay.faster(),
+            Key::Character(c) if c == "-" => replay.slower(),
+            _ => return
+        }
+
+        // A new generation means any pending tick is stale.
+        if replay.generation() != generation {
+            self.autoplay.reset();
+        }
+        if autoplay {
+            self.schedule_autoplay();
+        }
+
+        self.replay_advanced(before);
+        self.request_redraw();
+    }
+
+    fn spawn_cb<T: 'static>(&self, fut: impl Future<Output = T> + 'static, cb: impl for<'a> FnOnce(&'a mut App, T) + 'static) {
+        self.exec.spawn_cb(fut, cb).detach();
     }
 
     fn on_resize(&mut self, w: u32, h: u32) {
@@ -139,13 +525,9 @@ impl App {
             return;
         }
 
-        let (x, y, s) = if w >= h {
-            ((w - h) / 2, 0, h)
-        } else {
-            (0, (h - w) / 2, w)
-        };
-
-        self.transform = Transform::from_bbox(NonZeroRect::from_xywh(x as f32, y as f32, s as f32 / 100., s as f32 / 100.).unwrap());
+        self.layout = Layout::fit(w, h);
+        self.rend.set_layout(self.layout);
+        self.rend.set_density(self.layout.scale(), self.scale_factor as f32);
 
         self.sfc.as_mut().unwrap().resize(w.try_into().unwrap(), h.try_into().unwrap()).unwrap();
         
@@ -159,23 +541,13 @@ impl App {
             }
         });
 
-        self.mask = Some({
-            let mut mask = match self.mask.take() {
-                None => Mask::new(w, h).unwrap(),
-                Some(mask) => {
-                    let mut mask = mask.take();
-                    mask.resize((w as usize) * (h as usize), 0);
-                    Mask::from_vec(mask, sz).unwrap()
-                }
-            };
-            mask.clear();
-            mask.fill_path(
-                &PathBuilder::from_rect(Rect::from_xywh(0., 0., 100., 100.).unwrap()),
-                FillRule::Winding,
-                false,
-                self.transform
-            );
-            mask
+        self.mask = Some(match self.mask.take() {
+            None => Mask::new(w, h).unwrap(),
+            Some(mask) => {
+                let mut mask = mask.take();
+                mask.resize((w as usize) * (h as usize), 0);
+                Mask::from_vec(mask, sz).unwrap()
+            }
         });
     }
 }
@@ -189,7 +561,8 @@ impl ApplicationHandler<AsyncEvent> for App {
         } else {
             let win = event_loop.create_window(WindowAttributes::default()
                 .with_resizable(true)
-                .with_inner_size(PhysicalSize::new( 600, 600))).unwrap();
+                .with_inner_size(LogicalSize::new(800, 600))).unwrap();
+            self.scale_factor = win.scale_factor();
 
             let ctx = Context::new(event_loop.owned_display_handle()).unwrap();
             let sfc = Surface::new(&ctx, win).unwrap();
@@ -213,72 +586,86 @@ impl ApplicationHandler<AsyncEvent> for App {
         match event {
             CloseRequested => event_loop.exit(),
             RedrawRequested => {
-                self.rend.prepare(&self.board);
-
+                let scene = self.scene();
                 let fb = self.fb.as_mut().unwrap();
-                fb.fill(Color::WHITE);
-
-                self.rend.render(&mut fb.as_mut(), self.transform, self.mask.as_ref());
+                let (w, h) = (fb.width(), fb.height());
+                let pixels = self.rend.redraw(&scene, fb, self.mask.as_mut().unwrap());
+
+                // Only a buffer holding exactly the last frame can be patched
+                // up; anything older or unknown gets everything.
+                let mut buf = self.sfc.as_mut().unwrap().buffer_mut().unwrap();
+                let pixels = if buf.age() == 1 { pixels } else { vec![IntRect::from_xywh(0, 0, w, h).unwrap()] };
+                if !pixels.is_empty() {
+                    for rect in &pixels {
+                        blit(&mut buf, fb, *rect);
+                    }
+                    buf.present_with_damage(&pixels.iter().map(|r| softbuffer::Rect {
+                        x: r.x() as u32,
+                        y: r.y() as u32,
+                        width: r.width().try_into().unwrap(),
+                        height: r.height().try_into().unwrap()
+                    }).collect::<Vec<_>>()).unwrap();
+                } else {
+                    drop(buf);
+                }
 
-                let sfc = self.sfc.as_mut().unwrap();
-                let mut buf = sfc.buffer_mut().unwrap();
-                for (dst, src) in iter::zip(&mut buf[..], fb.pixels()) {
-                    let src = src.demultiply();
-                    *dst = u32::from_le_bytes([src.blue(), src.green(), src.red(), 0]);
+                if scene.is_animating() {
+                    self.redraw_within(FRAME_INTERVAL);
+                } else if scene.clock_running().is_some() {
+                    self.redraw_within(CLOCK_INTERVAL);
                 }
-                buf.present().unwrap();
             },
             CursorMoved { device_id: _, position } => {
-                self.last_mouse_pos = position;
+                self.last_mouse_pos = Some(position);
+                self.set_hover(self.cell_at(position));
+            },
+            CursorLeft { device_id: _ } => {
+                self.last_mouse_pos = None;
+                self.set_hover(None);
+            },
+            Touch(touch) => self.on_touch(touch),
+            KeyboardInput { device_id: _, event: KeyEvent { logical_key, state: ElementState::Pressed, .. }, is_synthetic: false } => {
+                self.key_pressed(&logical_key);
             },
             MouseInput { device_id: _, state: ElementState::Pressed, button: MouseButton::Left } => {
-                let mut pt = Point { x: self.last_mouse_pos.x as f32, y: self.last_mouse_pos.y as f32 };
-                self.transform.invert().unwrap().map_point(&mut pt);
-
-                if pt.x < 0. || pt.x > 100. || pt.y < 0. || pt.y > 100. {
-                    return
-                }
-
-                let x = (pt.x * (N as f32) / 100.) as u8;
-                let y = (pt.y * (N as f32) / 100.) as u8;
-
-                if self.board.turn() == Some(Player::O) && let Ok(nst) = self.board.do_move(x, y) {
-                    self.board = nst;
-                    self.sfc.as_ref().unwrap().window().request_redraw();
-
-                    if self.board.score().is_none() {
-                        let timer = self.timer_after(Duration::from_millis(200));
-                        self.spawn_cb(
-                            async move {
-                                let pos = unblock(move || maximize(nst, Player::X)).await;
-                                timer.await;
-                                let (x, y) = pos.unwrap();
-                                nst.do_move(x, y).unwrap()
-                            },
-                            |this, nst| {
-                                this.board = nst;
-                                this.sfc.as_ref().unwrap().window().request_redraw();
-                            }
-                        );
-                    }
+                if let Some((x, y)) = self.last_mouse_pos.and_then(|pos| self.cell_at(pos)) {
+                    self.select_cell(x, y);
                 }
             },
-            Resized(PhysicalSize { width, height }) => self.on_resize(width, height),
+            Resized(PhysicalSize { width, height }) => {
+                self.on_resize(width, height);
+                self.set_hover(self.last_mouse_pos.and_then(|pos| self.cell_at(pos)));
+            },
+            ScaleFactorChanged { scale_factor, mut inner_size_writer } => {
+                // Keep the same logical size on the new monitor. The Resized
+                // that follows rebuilds the transform.
+                let size = self.sfc.as_ref().unwrap().window().inner_size()
+                    .to_logical::<f64>(self.scale_factor)
+                    .to_physical(scale_factor);
+                let _ = inner_size_writer.request_inner_size(size);
+                self.scale_factor = scale_factor;
+
+                // The pointer's physical position no longer means anything
+                // until it moves again.
+                self.last_mouse_pos = None;
+                self.touch = None;
+                self.set_hover(None);
+            },
             _ => ()
         }
+
+        self.game_events();
     }
 
     fn new_events(&mut self, _event_loop: &ActiveEventLoop, cause: winit::event::StartCause) {
         if let StartCause::ResumeTimeReached { start, .. } = cause {
-            while let Some(t) = self.timers.peek_mut() && t.at <= start {
-                binary_heap::PeekMut::pop(t).set()
-            }
+            self.timers.fire(start);
         }
     }
 
     fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
-        event_loop.set_control_flow(match self.timers.peek() {
-            Some(t) => ControlFlow::WaitUntil(t.at),
+        event_loop.set_control_flow(match self.timers.next_deadline() {
+            Some(at) => ControlFlow::WaitUntil(at),
             None => ControlFlow::Wait
         });
     }
@@ -287,17 +674,75 @@ impl ApplicationHandler<AsyncEvent> for App {
         match event {
             AsyncEvent::Runnable(r) => {
                 r.run();
-                if let Some(cb) = self.async_cb.take() {
+                self.game.run_completions();
+                while let Some(cb) = self.exec.next_completion() {
                     cb(self);
                 }
+                self.game_events();
             }
         }
     }
 }
 
+/// Loads the theme named by `TTT_THEME`, falling back to `ttt-theme.conf` in
+/// the working directory and then to the default theme.
+fn load_theme() -> anyhow::Result<Theme> {
+    let path = env::var_os("TTT_THEME");
+    let required = path.is_some();
+    let path = path.unwrap_or_else(|| THEME_FILE.into());
+
+    match fs::read_to_string(&path) {
+        Ok(src) => Theme::parse(&src).map_err(|e| anyhow::anyhow!("{}: {e}", path.display())),
+        Err(e) if !required && e.kind() == io::ErrorKind::NotFound => Ok(Theme::default()),
+        Err(e) => Err(anyhow::anyhow!("{}: {e}", path.display()))
+    }
+}
+
 fn main() -> anyhow::Result<()> {
+    const USAGE: &str = "usage: ttt [--clock 5m|3m+2s|10s/move] [--engine CMD | --host ADDR | --join ADDR] | ttt <command> ...";
+
+    let mut args = env::args().skip(1).peekable();
+    if let Some(cmd) = args.next_if(|arg| !arg.starts_with("--")) {
+        return cli::run(&cmd, args);
+    }
+
+    let (mut control, mut engine, mut host, mut join) = (None, None, None, None);
+    while let Some(flag) = args.next() {
+        let value = args.next().context(USAGE)?;
+        match flag.as_str() {
+            "--clock" => control = Some(value.parse::<TimeControl>()?),
+            "--engine" => engine = Some(value),
+            "--host" => host = Some(value),
+            "--join" => join = Some(value),
+            _ => anyhow::bail!(USAGE)
+        }
+    }
+
+    let net = match (host, join) {
+        (None, None) => None,
+        _ if control.is_some() => anyhow::bail!("time controls aren't supported over the network"),
+        (Some(addr), None) => {
+            let listener = TcpListener::bind(&addr).with_context(|| addr.clone())?;
+            eprintln!("waiting for an opponent on {}", listener.local_addr()?);
+            Some(Session::host(&listener)?)
+        },
+        (None, Some(addr)) => Some(Session::join(&addr).with_context(|| addr.clone())?),
+        (Some(_), Some(_)) => anyhow::bail!(USAGE)
+    };
+
+    let engine = match engine {
+        None => None,
+        Some(_) if net.is_some() => anyhow::bail!(USAGE),
+        Some(cmd) => {
+            let mut words = cmd.split_whitespace().map(str::to_owned);
+            let program = words.next().context("empty engine command")?;
+            Some(External::spawn(&program, &words.collect::<Vec<_>>()).with_context(|| cmd.clone())?)
+        }
+    };
+
+    let theme = load_theme()?;
     let evt = EventLoop::with_user_event().build()?;
-    let mut app = App::new(evt.create_proxy());
+    let mut app = App::new(evt.create_proxy(), theme, control, net, engine);
     evt.run_app(&mut app)?;
     Ok(())
 }
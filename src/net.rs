@@ -0,0 +1,467 @@
+//! Two instances playing each other over TCP, one hosting and one joining.
+//! The protocol is line based:
+//!
+//! - `HELLO 1 <side>` opens the connection. The host sends the side the
+//!   joining player takes, and the joining player answers with the host's.
+//! - `MOVE <ply> <cell>` makes the `ply`th move of the game, e.g. `MOVE 3 b2`.
+//! - `REMATCH` offers a new game once this one is over, or accepts one. The
+//!   players swap sides for it.
+//! - `BYE` comes before the connection closes.
+//!
+//! Both sides check every move with [`State::do_move`](crate::game::State::do_move).
+//! Anything that doesn't fit the game they are playing means they are out of
+//! sync, and the connection is dropped.
+
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt::Display,
+    future,
+    io::{self, BufRead, BufReader, Write},
+    net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    task::Poll,
+    thread,
+    time::Duration
+};
+
+use atomic_waker::AtomicWaker;
+
+use crate::{
+    controller::{Event, GameController, Seat},
+    game::{cell_name, parse_cell, Player}
+};
+
+const VERSION: u32 = 1;
+
+/// How long the host waits for whoever connects to say hello.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long a guest waits for the host's hello, which may come late if the
+/// host is busy turning someone else away.
+const JOIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    Hello { version: u32, side: Player },
+    Move { ply: usize, x: u8, y: u8 },
+    Rematch,
+    Bye
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseMessageError;
+
+impl Display for ParseMessageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("invalid message")
+    }
+}
+
+impl Error for ParseMessageError {}
+
+impl Display for Message {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Message::Hello { version, side } => write!(f, "HELLO {version} {side:?}"),
+            Message::Move { ply, x, y } => write!(f, "MOVE {ply} {}", cell_name((x, y))),
+            Message::Rematch => f.write_str("REMATCH"),
+            Message::Bye => f.write_str("BYE")
+        }
+    }
+}
+
+impl FromStr for Message {
+    type Err = ParseMessageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<_> = s.split_ascii_whitespace().collect();
+        match words[..] {
+            ["HELLO", version, side] => Ok(Message::Hello {
+                version: version.parse().map_err(|_| ParseMessageError)?,
+                side: match side {
+                    "X" => Player::X,
+                    "O" => Player::O,
+                    _ => return Err(ParseMessageError)
+                }
+            }),
+            ["MOVE", ply, cell] => {
+                let (x, y) = parse_cell(cell).ok_or(ParseMessageError)?;
+                Ok(Message::Move { ply: ply.parse().map_err(|_| ParseMessageError)?, x, y })
+            },
+            ["REMATCH"] => Ok(Message::Rematch),
+            ["BYE"] => Ok(Message::Bye),
+            _ => Err(ParseMessageError)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetError {
+    /// The opponent left, or the connection broke.
+    Disconnected,
+    /// The opponent sent something that doesn't fit the game.
+    Desync
+}
+
+impl Display for NetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            NetError::Disconnected => "opponent disconnected",
+            NetError::Desync => "out of sync with the opponent"
+        })
+    }
+}
+
+impl Error for NetError {}
+
+/// Messages read off the connection, waiting to be received.
+#[derive(Default)]
+struct Inbox {
+    queue: Mutex<VecDeque<Result<Message, NetError>>>,
+    waker: AtomicWaker
+}
+
+impl Inbox {
+    fn push(&self, msg: Result<Message, NetError>) {
+        self.queue.lock().unwrap().push_back(msg);
+        self.waker.wake();
+    }
+}
+
+/// One end of a connection, past the handshake. A thread reads messages as
+/// they arrive. Dropping the peer closes the connection.
+pub struct Peer {
+    stream: TcpStream,
+    inbox: Arc<Inbox>
+}
+
+impl Peer {
+    /// Waits for someone to join on `listener`, who plays `side`. Whoever
+    /// connects without a proper handshake in time is turned away, and the
+    /// wait goes on.
+    pub fn host(listener: &TcpListener, side: Player) -> io::Result<Peer> {
+        loop {
+            let (stream, _) = listener.accept()?;
+            if let Ok(peer) = Self::greet(stream, side) {
+                return Ok(peer)
+            }
+        }
+    }
+
+    fn greet(stream: TcpStream, side: Player) -> io::Result<Peer> {
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut peer = Peer { stream, inbox: Arc::default() };
+
+        peer.write(Message::Hello { version: VERSION, side })?;
+        match read_message(&mut reader)? {
+            Message::Hello { version: VERSION, side: host } if host == side.other() => (),
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "handshake failed"))
+        }
+        peer.stream.set_read_timeout(None)?;
+        peer.listen(reader);
+        Ok(peer)
+    }
+
+    /// Joins a host at `addr`, returning the side it gave us.
+    pub fn join(addr: impl ToSocketAddrs) -> io::Result<(Peer, Player)> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_read_timeout(Some(JOIN_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut peer = Peer { stream, inbox: Arc::default() };
+
+        let Message::Hello { version: VERSION, side } = read_message(&mut reader)? else {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "handshake failed"))
+        };
+        peer.write(Message::Hello { version: VERSION, side: side.other() })?;
+        peer.stream.set_read_timeout(None)?;
+        peer.listen(reader);
+        Ok((peer, side))
+    }
+
+    fn listen(&mut self, reader: BufReader<TcpStream>) {
+        let inbox = self.inbox.clone();
+        thread::spawn(move || {
+            for line in reader.lines() {
+                let Ok(line) = line else { break };
+                match line.parse() {
+                    Ok(Message::Bye) => break,
+                    Ok(msg) => inbox.push(Ok(msg)),
+                    Err(ParseMessageError) => return inbox.push(Err(NetError::Desync))
+                }
+            }
+            inbox.push(Err(NetError::Disconnected));
+        });
+    }
+
+    fn write(&mut self, msg: Message) -> io::Result<()> {
+        writeln!(self.stream, "{msg}")
+    }
+
+    pub fn send(&mut self, msg: Message) -> Result<(), NetError> {
+        self.write(msg).map_err(|_| NetError::Disconnected)
+    }
+
+    /// The next message. The connection is done with once this fails.
+    pub fn recv(&self) -> impl Future<Output = Result<Message, NetError>> + 'static {
+        let inbox = self.inbox.clone();
+        future::poll_fn(move |cx| {
+            inbox.waker.register(cx.waker());
+            match inbox.queue.lock().unwrap().pop_front() {
+                Some(msg) => Poll::Ready(msg),
+                None => Poll::Pending
+            }
+        })
+    }
+}
+
+impl Drop for Peer {
+    fn drop(&mut self) {
+        let _ = self.write(Message::Bye);
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+fn read_message(reader: &mut impl BufRead) -> io::Result<Message> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into())
+    }
+    line.parse().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Plays a [`GameController`] against a peer: sends the local player's moves
+/// and applies the opponent's.
+pub struct Session {
+    peer: Peer,
+    side: Player,
+    /// Whether we offered a rematch.
+    offered: bool,
+    /// Whether the opponent offered one.
+    offered_by_peer: bool
+}
+
+impl Session {
+    /// Waits for an opponent on `listener`. The host plays X first.
+    pub fn host(listener: &TcpListener) -> io::Result<Self> {
+        Ok(Self::new(Peer::host(listener, Player::O)?, Player::X))
+    }
+
+    pub fn join(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let (peer, side) = Peer::join(addr)?;
+        Ok(Self::new(peer, side))
+    }
+
+    fn new(peer: Peer, side: Player) -> Self {
+        Self { peer, side, offered: false, offered_by_peer: false }
+    }
+
+    pub fn side(&self) -> Player {
+        self.side
+    }
+
+    /// A human here and the opponent on the other end.
+    pub fn seats(&self) -> [Seat; 2] {
+        match self.side {
+            Player::X => [Seat::Human, Seat::Remote],
+            Player::O => [Seat::Remote, Seat::Human]
+        }
+    }
+
+    pub fn offered(&self) -> bool {
+        self.offered
+    }
+
+    pub fn offered_by_peer(&self) -> bool {
+        self.offered_by_peer
+    }
+
+    /// See [`Peer::recv`].
+    pub fn recv(&self) -> impl Future<Output = Result<Message, NetError>> + 'static {
+        self.peer.recv()
+    }
+
+    /// Passes on the local player's moves, as the controller reports them.
+    pub fn on_game_event(&mut self, event: Event) -> Result<(), NetError> {
+        match event {
+            Event::Moved { player, x, y, ply } if player == self.side => self.peer.send(Message::Move { ply, x, y }),
+            _ => Ok(())
+        }
+    }
+
+    /// Applies a message from the opponent.
+    pub fn on_message(&mut self, ctl: &mut GameController, msg: Message) -> Result<(), NetError> {
+        match msg {
+            Message::Move { ply, x, y } if ply == ctl.record().len() + 1 => ctl.play_remote(x, y).map_err(|_| NetError::Desync),
+            Message::Rematch if self.offered => {
+                self.rematch(ctl);
+                Ok(())
+            },
+            Message::Rematch if ctl.outcome().is_some() => {
+                self.offered_by_peer = true;
+                Ok(())
+            },
+            _ => Err(NetError::Desync)
+        }
+    }
+
+    /// Offers a rematch, or accepts the opponent's offer. Does nothing while
+    /// the game is still on.
+    pub fn offer_rematch(&mut self, ctl: &mut GameController) -> Result<(), NetError> {
+        if ctl.outcome().is_none() || self.offered {
+            return Ok(())
+        }
+
+        self.peer.send(Message::Rematch)?;
+        if self.offered_by_peer {
+            self.rematch(ctl);
+        } else {
+            self.offered = true;
+        }
+        Ok(())
+    }
+
+    fn rematch(&mut self, ctl: &mut GameController) {
+        self.side = self.side.other();
+        self.offered = false;
+        self.offered_by_peer = false;
+        ctl.set_seats(self.seats());
+        ctl.new_game();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        iter,
+        pin::pin,
+        sync::mpsc,
+        task::{Context, Wake, Waker},
+        thread::Thread
+    };
+
+    use super::*;
+    use crate::driver::Driver;
+
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    fn block_on<T>(fut: impl Future<Output = T>) -> T {
+        let mut fut = pin!(fut);
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        loop {
+            if let Poll::Ready(v) = fut.as_mut().poll(&mut Context::from_waker(&waker)) {
+                return v
+            }
+            thread::park_timeout(Duration::from_secs(10));
+        }
+    }
+
+    /// A host and a guest connected over loopback, each with a controller.
+    fn connect(driver: &Driver<GameController>) -> [(Session, GameController); 2] {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let guest = thread::spawn(move || Session::join(addr).unwrap());
+        let host = Session::host(&listener).unwrap();
+        let guest = guest.join().unwrap();
+
+        [host, guest].map(|net| {
+            let mut ctl = GameController::new(driver.exec.clone(), driver.timers.clone(), net.seats());
+            ctl.new_game();
+            (net, ctl)
+        })
+    }
+
+    /// Plays a move for whoever is to move, and has the other end receive it.
+    fn play([a, b]: &mut [(Session, GameController); 2], x: u8, y: u8) {
+        let (mover, other) = if a.1.awaiting_human() { (a, b) } else { (b, a) };
+        mover.1.play(x, y).unwrap();
+        for event in iter::from_fn(|| mover.1.next_event()) {
+            mover.0.on_game_event(event).unwrap();
+        }
+        let msg = block_on(other.0.recv()).unwrap();
+        other.0.on_message(&mut other.1, msg).unwrap();
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        for msg in [Message::Hello { version: 1, side: Player::O }, Message::Move { ply: 12, x: 3, y: 0 }, Message::Rematch, Message::Bye] {
+            assert_eq!(msg.to_string().parse(), Ok(msg));
+        }
+        assert_eq!("MOVE 1 b2".parse(), Ok(Message::Move { ply: 1, x: 1, y: 1 }));
+        assert_eq!("MOVE b2".parse::<Message>(), Err(ParseMessageError));
+    }
+
+    #[test]
+    fn both_ends_play_the_same_game_then_swap_sides() {
+        let driver = Driver::new();
+        let mut ends = connect(&driver);
+        assert_eq!(ends.each_ref().map(|(net, _)| net.side()), [Player::X, Player::O]);
+
+        // X takes the top row while O dawdles on the second.
+        for (x, y) in [(0, 0), (0, 1), (1, 0), (1, 1), (2, 0), (2, 1), (3, 0)] {
+            play(&mut ends, x, y);
+        }
+        for (_, ctl) in &ends {
+            assert_eq!(ctl.record().to_string(), "a1 a2 b1 b2 c1 c2 d1");
+            assert_eq!(ctl.outcome(), Some(crate::game::Score::Win(Player::X)));
+        }
+
+        let [(host, host_ctl), (guest, guest_ctl)] = &mut ends;
+        guest.offer_rematch(guest_ctl).unwrap();
+        let msg = block_on(host.recv()).unwrap();
+        host.on_message(host_ctl, msg).unwrap();
+        assert!(host.offered_by_peer());
+        host.offer_rematch(host_ctl).unwrap();
+        let msg = block_on(guest.recv()).unwrap();
+        guest.on_message(guest_ctl, msg).unwrap();
+
+        assert_eq!([host.side(), guest.side()], [Player::O, Player::X]);
+        assert_eq!([host_ctl.record().len(), guest_ctl.record().len()], [0, 0]);
+        assert!(guest_ctl.awaiting_human() && !host_ctl.awaiting_human());
+    }
+
+    #[test]
+    fn an_impossible_move_is_a_desync() {
+        let driver = Driver::new();
+        let [(mut host, mut host_ctl), (guest, _)] = connect(&driver);
+        // O can't move first.
+        guest.peer.stream.try_clone().unwrap().write_all(b"MOVE 1 a1\n").unwrap();
+        let msg = block_on(host.recv()).unwrap();
+        assert_eq!(host.on_message(&mut host_ctl, msg), Err(NetError::Desync));
+        assert_eq!(host_ctl.record().len(), 0);
+    }
+
+    #[test]
+    fn leaving_is_noticed() {
+        let driver = Driver::new();
+        let [(host, _), (guest, _)] = connect(&driver);
+        drop(guest);
+        assert_eq!(block_on(host.recv()), Err(NetError::Disconnected));
+    }
+
+    #[test]
+    fn host_turns_strangers_away() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut chatty = TcpStream::connect(addr).unwrap();
+            chatty.write_all(b"GET / HTTP/1.1\n").unwrap();
+            // Says nothing at all, and holds the connection open meanwhile.
+            let _silent = TcpStream::connect(addr).unwrap();
+            let guest = Session::join(addr).unwrap();
+            rx.recv().unwrap();
+            drop((chatty, guest));
+        });
+        let host = Session::host(&listener).unwrap();
+        assert_eq!(host.side(), Player::X);
+        tx.send(()).unwrap();
+    }
+}
@@ -1,66 +1,505 @@
-use tiny_skia::{Color, LineCap, LineJoin, Mask, Paint, Path, PathBuilder, PixmapMut, Shader, Stroke, Transform};
+use std::{iter, time::Duration};
 
-use crate::{game::{Player, State}, N};
+use tiny_skia::{Color, FillRule, IntRect, LineCap, LineJoin, Mask, Paint, Path, PathBuilder, Pixmap, PixmapMut, Rect, Shader, Stroke, Transform};
 
-const GRID_COLOR: Color = unsafe { Color::from_rgba_unchecked(1., 159./255., 244./255., 1.) };
-const TILE_COLOR: Color = unsafe { Color::from_rgba_unchecked(216./255., 159./255., 1., 1.) };
-
-const GRID_PAINT: &Paint = &Paint {
-    shader: Shader::SolidColor(GRID_COLOR),
-    blend_mode: tiny_skia::BlendMode::SourceOver,
-    anti_alias: true,
-    force_hq_pipeline: false,
-    colorspace: tiny_skia::ColorSpace::Linear
+use crate::{
+    font::{self, Align},
+    game::{Player, Score, State, Tally},
+    theme::{MarkStyle, Theme},
+    N
 };
 
-const TILE_PAINT: &Paint = &Paint {
-    shader: Shader::SolidColor(TILE_COLOR),
-    blend_mode: tiny_skia::BlendMode::SourceOver,
-    anti_alias: true,
-    force_hq_pipeline: false,
-    colorspace: tiny_skia::ColorSpace::Linear
-};
+#[cfg(test)]
+mod golden;
+mod layout;
+mod svg;
 
-const STROKE: &Stroke = &Stroke {
-    width: 5. / 3.,
-    miter_limit: 4.,
-    line_cap: LineCap::Round,
-    line_join: LineJoin::Miter,
-    dash: None
-};
+pub use layout::{Layout, Region};
+pub use svg::Svg;
+
+fn solid(color: Color, anti_alias: bool) -> Paint<'static> {
+    Paint {
+        shader: Shader::SolidColor(color),
+        blend_mode: tiny_skia::BlendMode::SourceOver,
+        anti_alias,
+        force_hq_pipeline: false,
+        colorspace: tiny_skia::ColorSpace::Linear
+    }
+}
+
+fn stroke(width: f32, line_join: LineJoin) -> Stroke {
+    Stroke {
+        width,
+        miter_limit: 4.,
+        line_cap: LineCap::Round,
+        line_join,
+        dash: None
+    }
+}
+
+/// The thinnest stroke drawn, in logical pixels.
+const MIN_STROKE_PX: f32 = 1.;
+
+const PREVIEW_OPACITY: f32 = 0.35;
+const BANNER_OPACITY: f32 = 0.85;
+
+const LABEL_SIZE: f32 = 3.5;
+const STATUS_SIZE: f32 = 3.5;
+const BANNER_SIZE: f32 = 12.;
+
+const BANNER: (f32, f32) = (38., 62.);
+
+/// Spacing of the panel's lines of text, and the width of its columns.
+const LINE_HEIGHT: f32 = 6.;
+const COLUMN_WIDTH: f32 = 36.;
+
+/// Limits drawing to the pixels in `damage` that fall within `view`, as
+/// placed by `transform`.
+fn fill_damage_mask(mask: &mut Mask, damage: &[IntRect], view: Rect, transform: Transform) {
+    mask.clear();
+    for rect in damage {
+        mask.fill_path(&PathBuilder::from_rect(rect.to_rect()), FillRule::Winding, false, Transform::identity());
+    }
+    mask.intersect_path(&PathBuilder::from_rect(view), FillRule::Winding, false, transform);
+}
+
+/// Somewhere the prepared scene can be drawn. Paths arrive in world units,
+/// and are painted in solid colours.
+pub trait Canvas {
+    fn stroke_path(&mut self, path: &Path, color: Color, stroke: &Stroke);
+    /// Fills without anti-aliasing only for backgrounds, so that neighbours
+    /// meet without a seam.
+    fn fill_path(&mut self, path: &Path, color: Color, rule: FillRule, anti_alias: bool);
+}
+
+/// Rasterises into a pixmap, mapping world space through `transform` and
+/// clipping to `mask`.
+pub struct Raster<'a, 'b> {
+    pub target: PixmapMut<'a>,
+    pub transform: Transform,
+    pub mask: Option<&'b Mask>
+}
+
+impl Canvas for Raster<'_, '_> {
+    fn stroke_path(&mut self, path: &Path, color: Color, stroke: &Stroke) {
+        self.target.stroke_path(path, &solid(color, true), stroke, self.transform, self.mask);
+    }
+
+    fn fill_path(&mut self, path: &Path, color: Color, rule: FillRule, anti_alias: bool) {
+        self.target.fill_path(path, &solid(color, anti_alias), rule, self.transform, self.mask);
+    }
+}
+
+/// What changed since the previous [`Renderer::prepare`], in the
+/// coordinates of each region.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Damage {
+    Full,
+    Rects(Vec<(Region, Rect)>)
+}
+
+impl Damage {
+    /// The pixels of a `w` by `h` target laid out by `layout` that need
+    /// repainting. Anti-aliasing may touch one pixel past each rect.
+    pub fn pixels(&self, layout: &Layout, w: u32, h: u32) -> Vec<IntRect> {
+        let bounds = IntRect::from_xywh(0, 0, w, h).unwrap();
+        match self {
+            Damage::Full => vec![bounds],
+            Damage::Rects(rects) => rects.iter()
+                .filter_map(|&(region, r)| {
+                    r.transform(layout.transform(region))?.outset(1., 1.)?.round_out()?.intersect(&bounds)
+                })
+                .collect()
+        }
+    }
+}
 
 enum Drawable {
-    Stroke(Path, &'static Paint<'static>, &'static Stroke)
+    Stroke(Path, Color, Stroke),
+    Fill(Path, Color, FillRule),
+    /// A region's background, filled without anti-aliasing.
+    Background(Path, Color)
+}
+
+impl Drawable {
+    fn draw(&self, canvas: &mut impl Canvas) {
+        match *self {
+            Drawable::Stroke(ref path, color, ref stroke) => canvas.stroke_path(path, color, stroke),
+            Drawable::Fill(ref path, color, rule) => canvas.fill_path(path, color, rule, true),
+            Drawable::Background(ref path, color) => canvas.fill_path(path, color, FillRule::Winding, false)
+        }
+    }
 }
 
 impl From<Drawable> for PathBuilder {
     fn from(value: Drawable) -> Self {
         match value {
-            Drawable::Stroke(path, _, _) => path
+            Drawable::Stroke(path, _, _) | Drawable::Fill(path, _, _) | Drawable::Background(path, _) => path
         }.clear()
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Scene {
+    pub state: State,
+    pub last_move: Option<(u8, u8)>,
+    pub cursor: Option<(u8, u8)>,
+    /// A translucent mark shown on the hovered cell.
+    pub preview: Option<(u8, u8, Player)>,
+    /// The cell whose mark is still being placed, with its progress in `0..=1`.
+    pub placing: Option<(u8, u8, f32)>,
+    /// The winning line's end cells and how far it has been swept.
+    pub win_line: Option<((u8, u8), (u8, u8), f32)>,
+    pub tally: Tally,
+    /// The ply shown and the length of the game, when replaying.
+    pub replay: Option<(usize, usize)>,
+    /// The moves of the game so far, for the panel.
+    pub moves: [Option<(u8, u8)>; (N*N) as usize],
+    /// How the game ends with best play, once the AI has worked it out.
+    pub eval: Option<Score>,
+    /// Each player's time left, X first, when playing on time.
+    pub clocks: Option<[Duration; 2]>,
+    /// The player who lost on time.
+    pub flagged: Option<Player>,
+    /// Shown after the status, such as news from a remote opponent.
+    pub notice: Option<&'static str>
+}
+
+/// A still of the position: no highlights, and any winning line fully drawn.
+impl From<State> for Scene {
+    fn from(state: State) -> Self {
+        Scene {
+            state,
+            win_line: state.win_line().map(|(from, to)| (from, to, 1.)),
+            ..Default::default()
+        }
+    }
+}
+
+impl Scene {
+    pub fn with_moves(mut self, moves: &[(u8, u8)]) -> Self {
+        self.moves = Default::default();
+        for (slot, &mv) in iter::zip(&mut self.moves, moves) {
+            *slot = Some(mv);
+        }
+        self
+    }
+
+    /// Whose clock is running, if any.
+    pub fn clock_running(&self) -> Option<Player> {
+        self.state.turn().filter(|_| self.clocks.is_some() && self.replay.is_none() && self.flagged.is_none())
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.placing.is_some_and(|(_, _, t)| t < 1.) || self.win_line.is_some_and(|(_, _, t)| t < 1.)
+    }
+}
+
 #[derive(Default)]
 pub struct Renderer {
+    theme: Theme,
+    layout: Layout,
+    /// [`MIN_STROKE_PX`] in world units at the current size and scale.
+    min_stroke: f32,
     path_buffers: Vec<PathBuilder>,
-    paths: Vec<Drawable>
+    paths: Vec<(Region, Drawable)>,
+    /// Where drawables queued now end up.
+    region: Region,
+    /// The scene last prepared, unless the whole frame must be redrawn.
+    last: Option<Scene>
+}
+
+fn ease_out(t: f32) -> f32 {
+    1. - (1. - t.clamp(0., 1.)).powi(3)
+}
+
+fn lerp((x0, y0): (f32, f32), (x1, y1): (f32, f32), t: f32) -> (f32, f32) {
+    (x0 + (x1 - x0) * t, y0 + (y1 - y0) * t)
+}
+
+/// Strokes the X up to `t`: the first bar during the first half, the second
+/// bar during the second.
+fn draw_x(builder: &mut PathBuilder, x: u32, y: u32, t: f32) {
+    let bars = [
+        ((x as f32 + 0.2, y as f32 + 0.2), ((x + 1) as f32 - 0.2, (y + 1) as f32 - 0.2)),
+        (((x + 1) as f32 - 0.2, y as f32 + 0.2), (x as f32 + 0.2, (y + 1) as f32 - 0.2))
+    ];
+
+    for (k, (from, to)) in bars.into_iter().enumerate() {
+        let t = ease_out(2. * t - k as f32);
+        if t <= 0. { break }
+        let (ex, ey) = lerp(from, to, t);
+        builder.move_to(from.0, from.1);
+        builder.line_to(ex, ey);
+    }
+}
+
+/// Fills the X as two bars `width` cell units thick, growing like [`draw_x`].
+fn fill_x(builder: &mut PathBuilder, x: u32, y: u32, t: f32, width: f32) {
+    let bars = [
+        ((x as f32 + 0.2, y as f32 + 0.2), ((x + 1) as f32 - 0.2, (y + 1) as f32 - 0.2)),
+        (((x + 1) as f32 - 0.2, y as f32 + 0.2), (x as f32 + 0.2, (y + 1) as f32 - 0.2))
+    ];
+
+    for (k, (from, to)) in bars.into_iter().enumerate() {
+        let t = ease_out(2. * t - k as f32);
+        if t <= 0. { break }
+        let (ex, ey) = lerp(from, to, t);
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let len = dx.hypot(dy);
+        let (nx, ny) = (-dy / len * width / 2., dx / len * width / 2.);
+        builder.move_to(from.0 + nx, from.1 + ny);
+        builder.line_to(ex + nx, ey + ny);
+        builder.line_to(ex - nx, ey - ny);
+        builder.line_to(from.0 - nx, from.1 - ny);
+        builder.close();
+    }
+}
+
+fn draw_o(builder: &mut PathBuilder, x: u32, y: u32, t: f32) {
+    let t = ease_out(t);
+    if t > 0. {
+        builder.push_circle((x as f32) + 0.5, (y as f32) + 0.5, 0.3 * t);
+    }
 }
 
-fn draw_x(builder: &mut PathBuilder, x: u32, y: u32) {
-    builder.move_to(x as f32 + 0.2, y as f32 + 0.2);
-    builder.line_to((x + 1) as f32 - 0.2, (y + 1) as f32 - 0.2);
-    builder.move_to((x + 1) as f32 - 0.2, y as f32 + 0.2);
-    builder.line_to(x as f32 + 0.2, (y + 1) as f32 - 0.2);
+fn draw_win_line(builder: &mut PathBuilder, (x0, y0): (u8, u8), (x1, y1): (u8, u8), t: f32) {
+    let t = ease_out(t);
+    if t <= 0. { return }
+
+    // Run from just outside the first cell's mark to just outside the last's.
+    // Integer signum, since `f32::signum` maps zero to one.
+    let (dx, dy) = ((x1 as i8 - x0 as i8).signum() as f32, (y1 as i8 - y0 as i8).signum() as f32);
+    let from = (x0 as f32 + 0.5 - 0.35 * dx, y0 as f32 + 0.5 - 0.35 * dy);
+    let to = (x1 as f32 + 0.5 + 0.35 * dx, y1 as f32 + 0.5 + 0.35 * dy);
+    let (ex, ey) = lerp(from, to, t);
+    builder.move_to(from.0, from.1);
+    builder.line_to(ex, ey);
+}
+
+fn column_label(x: u32) -> char {
+    (b'A' + x as u8) as char
 }
 
-fn draw_o(builder: &mut PathBuilder, x: u32, y: u32) {
-    builder.push_circle((x as f32) + 0.5, (y as f32) + 0.5, 0.3);
+fn move_label((x, y): (u8, u8)) -> String {
+    format!("{}{}", column_label(x as u32), y + 1)
+}
+
+fn outcome(score: Score) -> String {
+    match score {
+        Score::Win(p) => format!("{p:?} WINS"),
+        Score::Tie => "DRAW".to_owned()
+    }
+}
+
+/// Minutes and seconds, or seconds and tenths once it gets tight.
+fn clock_label(left: Duration) -> String {
+    let secs = left.as_secs();
+    if secs < 10 {
+        format!("{secs}.{}", left.subsec_millis() / 100)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+fn status(scene: &Scene) -> String {
+    if let Some((ply, len)) = scene.replay {
+        return format!("REPLAY {ply}/{len}")
+    }
+    if let Some(p) = scene.flagged {
+        return format!("{} ON TIME", outcome(Score::Win(p.other())))
+    }
+
+    match (scene.state.score(), scene.state.turn()) {
+        (Some(score), _) => outcome(score),
+        (None, Some(p)) => format!("{p:?} TO MOVE"),
+        (None, None) => unreachable!()
+    }
+}
+
+/// The status bar's text: the status and any notice.
+fn status_line(scene: &Scene) -> String {
+    match scene.notice {
+        Some(notice) => format!("{}   {notice}", status(scene)),
+        None => status(scene)
+    }
+}
+
+/// Whether the result banner covers the board: once a finished game's
+/// winning line has been swept, outside replay.
+fn banner_shown(scene: &Scene) -> bool {
+    let settled = scene.win_line.is_none_or(|(_, _, t)| t >= 1.);
+    scene.replay.is_none() && (scene.state.score().is_some() || scene.flagged.is_some()) && settled
+}
+
+fn draw_highlight(builder: &mut PathBuilder, x: u32, y: u32) {
+    builder.push_rect(Rect::from_xywh(x as f32 + 0.08, y as f32 + 0.08, 0.84, 0.84).unwrap());
 }
 
 impl Renderer {
-    pub fn prepare(&mut self, st: &State) {
-        self.path_buffers.extend(self.paths.drain(..).map(Into::into));
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        self.last = None;
+    }
+
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
+        self.last = None;
+    }
+
+    /// Tells the renderer how many physical pixels a world unit covers and
+    /// the display's scale factor, so thin strokes stay visible everywhere.
+    pub fn set_density(&mut self, px_per_unit: f32, scale_factor: f32) {
+        self.min_stroke = MIN_STROKE_PX * scale_factor / px_per_unit;
+        self.last = None;
+    }
+
+    fn stroke(&self, width: f32, line_join: LineJoin) -> Stroke {
+        stroke(width.max(self.min_stroke), line_join)
+    }
+
+    /// Queues a path built in world units, returning the builder to the pool
+    /// if nothing was drawn.
+    fn push_world(&mut self, path_buffer: PathBuilder, drawable: impl FnOnce(Path) -> Drawable) {
+        if !path_buffer.is_empty() {
+            self.paths.push((self.region, drawable(path_buffer.finish().unwrap())));
+        } else {
+            self.path_buffers.push(path_buffer);
+        }
+    }
+
+    /// Scales a path built in cell units up to world units and queues it.
+    fn push(&mut self, path_buffer: PathBuilder, drawable: impl FnOnce(Path) -> Drawable) {
+        self.push_world(path_buffer, |path| {
+            drawable(path.transform(Transform::from_scale(100. / N as f32, 100. / N as f32)).unwrap())
+        });
+    }
+
+    fn push_stroke(&mut self, path_buffer: PathBuilder, color: Color, stroke: Stroke) {
+        self.push(path_buffer, |path| Drawable::Stroke(path, color, stroke));
+    }
+
+    fn push_text(&mut self, s: &str, x: f32, y: f32, size: f32, align: Align, color: Color) {
+        let mut path_buffer = self.path_buffers.pop().unwrap_or_default();
+        font::text(&mut path_buffer, s, x, y, size, align);
+        let stroke = self.stroke(0.13 * size, LineJoin::Round);
+        self.push_world(path_buffer, |path| Drawable::Stroke(path, color, stroke));
+    }
+
+    /// Queues marks built with [`Self::draw_mark`] in their player's style.
+    fn push_mark(&mut self, path_buffer: PathBuilder, style: MarkStyle, opacity: f32) {
+        let mut color = style.color;
+        color.apply_opacity(opacity);
+        if style.filled {
+            self.push(path_buffer, |path| Drawable::Fill(path, color, FillRule::Winding));
+        } else {
+            self.push_stroke(path_buffer, color, self.stroke(style.width, LineJoin::Miter));
+        }
+    }
+
+    fn draw_mark(path_buffer: &mut PathBuilder, player: Player, style: MarkStyle, x: u32, y: u32, t: f32) {
+        match (player, style.filled) {
+            (Player::X, false) => draw_x(path_buffer, x, y, t),
+            (Player::X, true) => fill_x(path_buffer, x, y, t, style.width * N as f32 / 100.),
+            (Player::O, _) => draw_o(path_buffer, x, y, t)
+        }
+    }
+
+    /// Works out which parts of the frame differ between the last prepared
+    /// scene and `scene`: cells whose contents or highlights changed, the
+    /// board if the winning line moved, and the status bar and banner if
+    /// their text did.
+    fn damage(&self, scene: &Scene) -> Damage {
+        let Some(last) = self.last else { return Damage::Full };
+        if last == *scene {
+            return Damage::Rects(Vec::new())
+        }
+
+        let mut cells = Vec::new();
+        for (i, (a, b)) in iter::zip(last.state.board(), scene.state.board()).enumerate() {
+            if a != b {
+                cells.push(((i as u32 % N) as u8, (i as u32 / N) as u8));
+            }
+        }
+
+        let mut changed = |changed: bool, a: Option<(u8, u8)>, b: Option<(u8, u8)>| {
+            if changed {
+                cells.extend(a);
+                cells.extend(b);
+            }
+        };
+        changed(last.last_move != scene.last_move, last.last_move, scene.last_move);
+        changed(last.cursor != scene.cursor, last.cursor, scene.cursor);
+        let (p, q) = (last.preview.map(|(x, y, _)| (x, y)), scene.preview.map(|(x, y, _)| (x, y)));
+        changed(last.preview != scene.preview, p, q);
+        let (p, q) = (last.placing.map(|(x, y, _)| (x, y)), scene.placing.map(|(x, y, _)| (x, y)));
+        changed(last.placing != scene.placing, p, q);
+
+        // Enough to cover any stroke centred on a cell's edge or poking out
+        // of its mark.
+        let theme = &self.theme;
+        let margin = theme.x.width.max(theme.o.width).max(1.5 * theme.stroke_width).max(self.min_stroke);
+        let cell = 100. / N as f32;
+        let mut rects: Vec<(Region, Rect)> = cells.into_iter()
+            .filter_map(|(x, y)| Rect::from_xywh(x as f32 * cell, y as f32 * cell, cell, cell)?.outset(margin, margin))
+            .map(|r| (Region::Board, r))
+            .collect();
+
+        if last.win_line != scene.win_line {
+            rects.extend(Rect::from_ltrb(0., 0., 100., 100.).unwrap().outset(margin, margin).map(|r| (Region::Board, r)));
+        }
+
+        if status_line(&last) != status_line(scene) {
+            rects.push((Region::Status, self.layout.view(Region::Status)));
+        }
+        let status_changed = status(&last) != status(scene);
+
+        let (was_shown, shown) = (banner_shown(&last), banner_shown(scene));
+        if was_shown != shown || shown && status_changed {
+            rects.extend(Rect::from_ltrb(0., BANNER.0, 100., BANNER.1).unwrap().outset(margin, margin).map(|r| (Region::Board, r)));
+        }
+
+        let panel = |s: &Scene| (s.moves, s.replay, s.tally, s.eval, s.clocks.map(|c| c.map(clock_label)), s.clock_running());
+        if panel(&last) != panel(scene) {
+            rects.push((Region::Panel, self.layout.view(Region::Panel)));
+        }
+
+        Damage::Rects(rects)
+    }
+
+    /// Switches to queueing drawables for `region`, over its background.
+    fn begin(&mut self, region: Region) {
+        self.region = region;
+        let mut path_buffer = self.path_buffers.pop().unwrap_or_default();
+        path_buffer.push_rect(self.layout.view(region));
+        let color = self.theme.background;
+        self.push_world(path_buffer, |path| Drawable::Background(path, color));
+    }
+
+    /// Builds the drawables for `scene`, returning what changed since the
+    /// previous call.
+    pub fn prepare(&mut self, scene: &Scene) -> Damage {
+        let damage = self.damage(scene);
+        self.last = Some(*scene);
+
+        self.path_buffers.extend(self.paths.drain(..).map(|(_, drawable)| drawable.into()));
+
+        let theme = self.theme;
+        self.begin(Region::Board);
+        for (cell, color) in [(scene.last_move, theme.last_move), (scene.cursor, theme.cursor)] {
+            let Some((x, y)) = cell else { continue };
+            let mut path_buffer = self.path_buffers.pop().unwrap_or_default();
+            draw_highlight(&mut path_buffer, x as u32, y as u32);
+            self.push_stroke(path_buffer, color, self.stroke(0.4 * theme.stroke_width, LineJoin::Round));
+        }
 
         {
             let mut path_buffer = self.path_buffers.pop().unwrap_or_default();
@@ -73,52 +512,202 @@ impl Renderer {
                 path_buffer.line_to((N as f32) - 0.1, k);
             }
 
-            if !path_buffer.is_empty() {
-                let path = path_buffer.finish().unwrap().transform(Transform::from_scale(100. / N as f32, 100. / N as f32)).unwrap();
-                self.paths.push(Drawable::Stroke(
-                    path,
-                    GRID_PAINT,
-                    STROKE
-                ));
-            } else {
-                self.path_buffers.push(path_buffer);
-            }
+            self.push_stroke(path_buffer, theme.grid, self.stroke(theme.stroke_width, LineJoin::Miter));
         }
 
-        {
+        for (player, style) in [(Player::X, theme.x), (Player::O, theme.o)] {
             let mut path_buffer = self.path_buffers.pop().unwrap_or_default();
 
-            for (i, player) in st.board().into_iter().enumerate() {
+            for (i, p) in scene.state.board().into_iter().enumerate() {
+                if p != Some(player) { continue }
                 let i = i as u32;
                 let x = i % N;
                 let y = i / N;
-                if player == Some(Player::X) {
-                    draw_x(&mut path_buffer, x, y);
-                } else if player == Some(Player::O) {
-                    draw_o(&mut path_buffer, x, y);
-                }
+                let t = match scene.placing {
+                    Some((px, py, t)) if (px as u32, py as u32) == (x, y) => t,
+                    _ => 1.
+                };
+                Self::draw_mark(&mut path_buffer, player, style, x, y, t);
             }
 
-            if !path_buffer.is_empty() {
-                let path = path_buffer.finish().unwrap().transform(Transform::from_scale(100. / N as f32, 100. / N as f32)).unwrap();
-                self.paths.push(Drawable::Stroke(
-                    path,
-                    TILE_PAINT,
-                    STROKE
-                ));
-            } else {
-                self.path_buffers.push(path_buffer);
+            self.push_mark(path_buffer, style, 1.);
+        }
+
+        if let Some((x, y, player)) = scene.preview {
+            let mut path_buffer = self.path_buffers.pop().unwrap_or_default();
+            let style = match player {
+                Player::X => theme.x,
+                Player::O => theme.o
+            };
+            Self::draw_mark(&mut path_buffer, player, style, x as u32, y as u32, 1.);
+            self.push_mark(path_buffer, style, PREVIEW_OPACITY);
+        }
+
+        if let Some((from, to, t)) = scene.win_line {
+            let mut path_buffer = self.path_buffers.pop().unwrap_or_default();
+            draw_win_line(&mut path_buffer, from, to, t);
+            self.push_stroke(path_buffer, theme.win, self.stroke(1.5 * theme.stroke_width, LineJoin::Miter));
+        }
+
+        let cell = 100. / N as f32;
+        for k in 0..N {
+            let mid = (k as f32 + 0.5) * cell;
+            self.push_text(&column_label(k).to_string(), mid, -2., LABEL_SIZE, Align::Center, theme.text);
+            self.push_text(&(k + 1).to_string(), -2., mid + LABEL_SIZE / 2., LABEL_SIZE, Align::Right, theme.text);
+        }
+
+        if banner_shown(scene) {
+            let mut path_buffer = self.path_buffers.pop().unwrap_or_default();
+            path_buffer.push_rect(Rect::from_ltrb(0., BANNER.0, 100., BANNER.1).unwrap());
+            let mut color = theme.background;
+            color.apply_opacity(BANNER_OPACITY);
+            self.push_world(path_buffer, |path| Drawable::Fill(path, color, FillRule::Winding));
+
+            self.push_text(&status(scene), 50., 50. + BANNER_SIZE / 2., BANNER_SIZE, Align::Center, theme.text);
+        }
+
+        self.begin(Region::Status);
+        let view = self.layout.view(Region::Status);
+        let mid = (view.top() + view.bottom()) / 2.;
+        self.push_text(&status_line(scene), layout::MARGIN, mid + STATUS_SIZE / 2., STATUS_SIZE, Align::Left, theme.text);
+
+        self.begin(Region::Panel);
+        self.prepare_panel(scene);
+
+        damage
+    }
+
+    /// Lists the moves, the running score, the AI's verdict and the clocks,
+    /// flowing into as many columns as the panel's height calls for.
+    fn prepare_panel(&mut self, scene: &Scene) {
+        let theme = self.theme;
+        let mut dim = theme.text;
+        dim.apply_opacity(PREVIEW_OPACITY);
+
+        // Each line is a list of pieces: text, indent and colour.
+        let mut lines: Vec<Vec<(String, f32, Color)>> = vec![vec![("MOVES".to_owned(), 0., theme.text)]];
+        let moves: Vec<_> = scene.moves.into_iter().map_while(|m| m).collect();
+        let shown = scene.replay.map_or(moves.len(), |(ply, _)| ply);
+        for (i, pair) in moves.chunks(2).enumerate() {
+            let mut line = vec![(format!("{}.", i + 1), 0., theme.text)];
+            for (k, &mv) in pair.iter().enumerate() {
+                let ply = 2 * i + k + 1;
+                let color = if ply == shown { theme.cursor } else if ply < shown { theme.text } else { dim };
+                line.push((move_label(mv), 8. + 9. * k as f32, color));
+            }
+            lines.push(line);
+        }
+
+        let Tally { x, o, ties } = scene.tally;
+        lines.push(Vec::new());
+        lines.push(vec![("SCORE".to_owned(), 0., theme.text)]);
+        for (label, n) in [("X", x), ("O", o), ("DRAWS", ties)] {
+            lines.push(vec![(label.to_owned(), 0., theme.text), (n.to_string(), 20., theme.text)]);
+        }
+
+        lines.push(Vec::new());
+        lines.push(vec![("AI".to_owned(), 0., theme.text)]);
+        lines.push(vec![(scene.eval.map_or("-".to_owned(), outcome), 0., theme.text)]);
+
+        if let Some(clocks) = scene.clocks {
+            lines.push(Vec::new());
+            lines.push(vec![("CLOCK".to_owned(), 0., theme.text)]);
+            for (p, left) in iter::zip([Player::X, Player::O], clocks) {
+                let color = if scene.clock_running() == Some(p) { theme.cursor } else { theme.text };
+                lines.push(vec![(format!("{p:?}"), 0., color), (clock_label(left), 8., color)]);
+            }
+        }
+
+        let view = self.layout.view(Region::Panel);
+        let rows = (((view.height() - 2. * layout::MARGIN) / LINE_HEIGHT) as usize).max(1);
+        for (i, line) in lines.into_iter().enumerate() {
+            let x = layout::MARGIN + (i / rows) as f32 * COLUMN_WIDTH;
+            let y = layout::MARGIN + (i % rows + 1) as f32 * LINE_HEIGHT;
+            for (text, indent, color) in line {
+                self.push_text(&text, x + indent, y, STATUS_SIZE, Align::Left, color);
+            }
+        }
+    }
+
+    /// Draws what was prepared for `region`, in its own coordinates.
+    pub fn render(&self, canvas: &mut impl Canvas, region: Region) {
+        for (r, drawable) in &self.paths {
+            if *r == region {
+                drawable.draw(canvas);
             }
         }
     }
 
-    pub fn render(&self, target: &mut PixmapMut<'_>, world_transform: Transform, world_mask: Option<&Mask>) {
-        for drawable in &self.paths {
-            match *drawable {
-                Drawable::Stroke(ref path, paint, stroke) => {
-                    target.stroke_path(path, paint, stroke, world_transform, world_mask);
-                }
+    /// Like [`Self::render`], but skips whatever lies outside `damage`.
+    /// Callers still clip to it, since drawables are only culled whole.
+    pub fn render_damage(&self, canvas: &mut impl Canvas, region: Region, damage: &Damage) {
+        let Damage::Rects(rects) = damage else { return self.render(canvas, region) };
+        for (r, drawable) in &self.paths {
+            if *r != region { continue }
+            let bounds = match *drawable {
+                // Miter joins can reach out twice the stroke width.
+                Drawable::Stroke(ref path, _, ref stroke) => path.bounds().outset(2. * stroke.width, 2. * stroke.width),
+                Drawable::Fill(ref path, _, _) | Drawable::Background(ref path, _) => Some(path.bounds())
+            };
+            let hit = |b: Rect| rects.iter().any(|&(r, d)| r == region && d.intersect(&b).is_some());
+            if bounds.is_some_and(hit) {
+                drawable.draw(canvas);
             }
         }
     }
-}
\ No newline at end of file
+
+    /// Brings `target`, last drawn by this renderer, up to date with `scene`,
+    /// repainting only what changed. `mask` is scratch space the size of
+    /// `target`. Returns the pixels repainted.
+    pub fn redraw(&mut self, scene: &Scene, target: &mut Pixmap, mask: &mut Mask) -> Vec<IntRect> {
+        let damage = self.prepare(scene);
+        let pixels = damage.pixels(&self.layout, target.width(), target.height());
+        if pixels.is_empty() {
+            return pixels
+        }
+
+        let mut paint = solid(self.theme.letterbox, true);
+        paint.blend_mode = tiny_skia::BlendMode::Source;
+        for rect in &pixels {
+            target.fill_rect(rect.to_rect(), &paint, Transform::identity(), None);
+        }
+
+        for region in Region::ALL {
+            let transform = self.layout.transform(region);
+            fill_damage_mask(mask, &pixels, self.layout.view(region), transform);
+            self.render_damage(&mut Raster { target: target.as_mut(), transform, mask: Some(mask) }, region, &damage);
+        }
+        pixels
+    }
+
+    /// Renders `scene` into a new `w` by `h` pixmap, as the window would.
+    pub fn render_offscreen(&mut self, scene: &Scene, w: u32, h: u32) -> Option<Pixmap> {
+        let mut pixmap = Pixmap::new(w, h)?;
+        let mut mask = Mask::new(w, h)?;
+        let layout = Layout::fit(w, h);
+        self.set_layout(layout);
+        self.set_density(layout.scale(), 1.);
+        self.redraw(scene, &mut pixmap, &mut mask);
+        Some(pixmap)
+    }
+
+    /// Adds what was prepared to `svg`, each region in a group placing it
+    /// within [`Layout::extent`].
+    pub fn render_svg_regions(&self, svg: &mut Svg) {
+        for region in Region::ALL {
+            let Transform { sx, ky, kx, sy, tx, ty } = self.layout.placement(region);
+            svg.raw(&format!("<g transform=\"matrix({sx} {ky} {kx} {sy} {tx} {ty})\">\n"));
+            self.render(svg, region);
+            svg.raw("</g>\n");
+        }
+    }
+
+    /// Renders `scene` as a standalone SVG document, laid out in landscape.
+    pub fn render_svg(&mut self, scene: &Scene) -> String {
+        self.set_layout(Layout::default());
+        self.prepare(scene);
+        let mut svg = Svg::default();
+        self.render_svg_regions(&mut svg);
+        svg.document(self.layout.extent(), self.theme.letterbox)
+    }
+}
@@ -0,0 +1,180 @@
+//! Renders canonical positions offscreen and compares them with the
+//! reference images in `tests/golden`. Set `TTT_BLESS=1` to rewrite the
+//! references after an intended change. Failures leave the actual image and
+//! a diff next to each other in `target/golden-diff`.
+
+use std::{env, fs, path::PathBuf};
+
+use tiny_skia::{Color, Pixmap};
+
+use crate::{game::{Player, State}, theme::Theme};
+
+use super::{Layout, Renderer, Scene};
+
+const SIZES: &[(u32, u32)] = &[(128, 128), (300, 200), (200, 300), (517, 263)];
+
+/// How far apart two channels may be before a pixel counts as different.
+const CHANNEL_TOLERANCE: u8 = 24;
+/// The share of pixels that may differ. Covers anti-aliasing drift between
+/// rasteriser versions without hiding a misplaced stroke.
+const PIXEL_TOLERANCE: f64 = 0.005;
+
+fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+fn diff_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/golden-diff")
+}
+
+/// Returns the number of differing pixels and an image marking them in red
+/// over a faded copy of the expected image.
+fn compare(expected: &Pixmap, actual: &Pixmap) -> (usize, Pixmap) {
+    let mut diff = Pixmap::new(expected.width(), expected.height()).unwrap();
+    let mut count = 0;
+
+    for ((e, a), d) in expected.pixels().iter().zip(actual.pixels()).zip(diff.pixels_mut()) {
+        let (e, a) = (e.demultiply(), a.demultiply());
+        let differs = [(e.red(), a.red()), (e.green(), a.green()), (e.blue(), a.blue()), (e.alpha(), a.alpha())]
+            .into_iter()
+            .any(|(e, a)| e.abs_diff(a) > CHANNEL_TOLERANCE);
+
+        let color = if differs {
+            count += 1;
+            Color::from_rgba8(255, 0, 0, 255)
+        } else {
+            let luma = (e.red() as u16 + e.green() as u16 + e.blue() as u16) / 3;
+            let faded = (192 + luma / 4) as u8;
+            Color::from_rgba8(faded, faded, faded, 255)
+        };
+        *d = color.premultiply().to_color_u8();
+    }
+
+    (count, diff)
+}
+
+fn check(name: &str, theme: Theme, scene: Scene) {
+    let bless = env::var_os("TTT_BLESS").is_some();
+    let mut rend = Renderer::default();
+    rend.set_theme(theme);
+
+    let mut failures = Vec::new();
+    for &(w, h) in SIZES {
+        let file = format!("{name}-{w}x{h}.png");
+        let actual = rend.render_offscreen(&scene, w, h).unwrap();
+        let reference = golden_dir().join(&file);
+
+        if bless {
+            fs::create_dir_all(golden_dir()).unwrap();
+            actual.save_png(&reference).unwrap();
+            continue
+        }
+
+        let Ok(expected) = Pixmap::load_png(&reference) else {
+            failures.push(format!("{file}: missing reference (run with TTT_BLESS=1)"));
+            continue
+        };
+
+        if (expected.width(), expected.height()) != (w, h) {
+            failures.push(format!("{file}: reference is {}x{}", expected.width(), expected.height()));
+            continue
+        }
+
+        let (count, diff) = compare(&expected, &actual);
+        if count as f64 > PIXEL_TOLERANCE * (w * h) as f64 {
+            fs::create_dir_all(diff_dir()).unwrap();
+            actual.save_png(diff_dir().join(format!("{name}-{w}x{h}.actual.png"))).unwrap();
+            diff.save_png(diff_dir().join(format!("{name}-{w}x{h}.diff.png"))).unwrap();
+            failures.push(format!("{file}: {count} pixels differ"));
+        }
+    }
+
+    assert!(failures.is_empty(), "golden images differ:\n{}", failures.join("\n"));
+}
+
+fn position(s: &str) -> State {
+    s.parse().unwrap()
+}
+
+#[test]
+fn empty_board() {
+    check("empty", Theme::LIGHT, State::default().into());
+}
+
+#[test]
+fn midgame() {
+    check("midgame", Theme::LIGHT, position("xo../.x../..o./....").into());
+}
+
+#[test]
+fn x_wins_diagonal() {
+    check("x-wins", Theme::LIGHT, position("xoo./.x../..xo/...x").into());
+}
+
+#[test]
+fn o_wins_column() {
+    check("o-wins", Theme::DARK, position("xo.x/.o../xo../.o.x").into());
+}
+
+#[test]
+fn draw() {
+    check("draw", Theme::HIGH_CONTRAST, position("xoxo/xoxo/oxox/oxox").into());
+}
+
+#[test]
+fn highlights_and_preview() {
+    let scene = Scene {
+        last_move: Some((1, 1)),
+        cursor: Some((3, 0)),
+        preview: Some((2, 2, Player::O)),
+        ..position("x.../.x../..../o...").into()
+    };
+    check("highlights", Theme::OKABE_ITO, scene);
+}
+
+/// Drawing a game frame by frame, repainting only the damage each time, must
+/// end up where a full redraw of every frame would.
+#[test]
+fn damage_matches_full_redraw() {
+    let (w, h) = (300, 200);
+    let layout = Layout::fit(w, h);
+    let mut rend = Renderer::default();
+    rend.set_theme(Theme::DARK);
+    rend.set_layout(layout);
+    rend.set_density(layout.scale(), 1.);
+    let mut pixmap = Pixmap::new(w, h).unwrap();
+    let mut mask = tiny_skia::Mask::new(w, h).unwrap();
+
+    let frames = [
+        Scene { cursor: Some((0, 0)), ..State::default().into() },
+        Scene { cursor: Some((1, 0)), preview: Some((2, 1, Player::O)), ..State::default().into() },
+        Scene { last_move: Some((1, 1)), placing: Some((1, 1, 0.5)), ..position("x.../.o../..../....").into() },
+        Scene { last_move: Some((1, 1)), ..position("x.../.o../..../....").into() },
+        Scene { replay: Some((2, 7)), ..position("x.../.o../..../....").into() },
+        Scene { last_move: Some((3, 3)), win_line: Some(((0, 0), (3, 3), 0.4)), ..position("xoo./.x../..xo/...x").into() },
+        position("xoo./.x../..xo/...x").into()
+    ];
+
+    let mut full = Renderer::default();
+    full.set_theme(Theme::DARK);
+    for (i, scene) in frames.iter().enumerate() {
+        let pixels = rend.redraw(scene, &mut pixmap, &mut mask);
+        // Rects may overlap, so count each pixel once.
+        let mut covered = vec![false; (w * h) as usize];
+        for r in &pixels {
+            for y in r.top()..r.bottom() {
+                covered[(y * w as i32 + r.left()) as usize..(y * w as i32 + r.right()) as usize].fill(true);
+            }
+        }
+        let area = covered.iter().filter(|&&c| c).count() as u32;
+        assert!(i == 0 || area < w * h, "frame {i} redrew everything");
+        if i == 1 {
+            // Moving the cursor and preview touches three cells.
+            assert!(area < w * h / 4, "moving the cursor redrew {area} pixels");
+        }
+
+        let expected = full.render_offscreen(scene, w, h).unwrap();
+        let (count, _) = compare(&expected, &pixmap);
+        assert_eq!(count, 0, "frame {i} differs from a full redraw");
+    }
+}
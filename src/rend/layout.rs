@@ -0,0 +1,110 @@
+//! Splits the target into the board, a side panel and a status bar, each
+//! drawn in its own coordinates. The panel sits right of the board in
+//! landscape and below it in portrait, and the status bar runs along the
+//! bottom.
+
+use tiny_skia::{Rect, Transform};
+
+/// Room left of and above the board for its coordinate labels.
+pub const MARGIN: f32 = 7.;
+
+/// The board view is the board plus [`MARGIN`] all round.
+const BOARD_SPAN: f32 = 100. + 2. * MARGIN;
+/// How far the panel reaches out from the board.
+const PANEL_SPAN: f32 = 50.;
+const STATUS_HEIGHT: f32 = 10.;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Region {
+    #[default]
+    Board,
+    Panel,
+    Status
+}
+
+impl Region {
+    pub const ALL: [Region; 3] = [Region::Board, Region::Panel, Region::Status];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Layout {
+    landscape: bool,
+    /// Maps layout units, which match world units everywhere, to pixels.
+    transform: Transform
+}
+
+/// Landscape at one pixel per unit, as used for SVG.
+impl Default for Layout {
+    fn default() -> Self {
+        Self { landscape: true, transform: Transform::identity() }
+    }
+}
+
+impl Layout {
+    fn size_of(landscape: bool) -> (f32, f32) {
+        if landscape {
+            (BOARD_SPAN + PANEL_SPAN, BOARD_SPAN + STATUS_HEIGHT)
+        } else {
+            (BOARD_SPAN, BOARD_SPAN + PANEL_SPAN + STATUS_HEIGHT)
+        }
+    }
+
+    /// Picks whichever orientation shows the board larger in a `w` by `h`
+    /// target and centres it there.
+    pub fn fit(w: u32, h: u32) -> Self {
+        let scale = |landscape| {
+            let (lw, lh) = Self::size_of(landscape);
+            (w as f32 / lw).min(h as f32 / lh)
+        };
+        let landscape = scale(true) >= scale(false);
+        let s = scale(landscape);
+        let (lw, lh) = Self::size_of(landscape);
+        let (x, y) = ((w as f32 - s * lw) / 2., (h as f32 - s * lh) / 2.);
+        Self { landscape, transform: Transform::from_row(s, 0., 0., s, x, y) }
+    }
+
+    /// Pixels per world unit.
+    pub fn scale(&self) -> f32 {
+        self.transform.sx
+    }
+
+    /// Everything laid out, in layout units.
+    pub fn extent(&self) -> Rect {
+        let (w, h) = Self::size_of(self.landscape);
+        Rect::from_xywh(0., 0., w, h).unwrap()
+    }
+
+    /// The part of a region's own coordinates that is shown. For the board,
+    /// 0 to 100 on both axes is the board itself.
+    pub fn view(&self, region: Region) -> Rect {
+        let (w, h) = match (region, self.landscape) {
+            (Region::Board, _) => return Rect::from_ltrb(-MARGIN, -MARGIN, 100. + MARGIN, 100. + MARGIN).unwrap(),
+            (Region::Panel, true) => (PANEL_SPAN, BOARD_SPAN),
+            (Region::Panel, false) => (BOARD_SPAN, PANEL_SPAN),
+            (Region::Status, _) => (self.extent().width(), STATUS_HEIGHT)
+        };
+        Rect::from_xywh(0., 0., w, h).unwrap()
+    }
+
+    /// Where the top left of a region's view sits, in layout units.
+    fn origin(&self, region: Region) -> (f32, f32) {
+        match (region, self.landscape) {
+            (Region::Board, _) => (0., 0.),
+            (Region::Panel, true) => (BOARD_SPAN, 0.),
+            (Region::Panel, false) => (0., BOARD_SPAN),
+            (Region::Status, _) => (0., self.extent().height() - STATUS_HEIGHT)
+        }
+    }
+
+    /// Maps a region's coordinates to layout units.
+    pub fn placement(&self, region: Region) -> Transform {
+        let view = self.view(region);
+        let (x, y) = self.origin(region);
+        Transform::from_translate(x - view.left(), y - view.top())
+    }
+
+    /// Maps a region's coordinates to pixels.
+    pub fn transform(&self, region: Region) -> Transform {
+        self.transform.pre_concat(self.placement(region))
+    }
+}
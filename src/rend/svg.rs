@@ -0,0 +1,112 @@
+use std::fmt::Write as _;
+
+use tiny_skia::{Color, FillRule, LineCap, LineJoin, Path, PathSegment, Rect, Stroke};
+
+use super::Canvas;
+
+fn hex(color: Color) -> String {
+    let c = color.to_color_u8();
+    format!("#{:02x}{:02x}{:02x}", c.red(), c.green(), c.blue())
+}
+
+fn path_data(path: &Path) -> String {
+    let mut d = String::new();
+    for segment in path.segments() {
+        match segment {
+            PathSegment::MoveTo(p) => write!(d, "M{} {}", p.x, p.y),
+            PathSegment::LineTo(p) => write!(d, "L{} {}", p.x, p.y),
+            PathSegment::QuadTo(p1, p) => write!(d, "Q{} {} {} {}", p1.x, p1.y, p.x, p.y),
+            PathSegment::CubicTo(p1, p2, p) => write!(d, "C{} {} {} {} {} {}", p1.x, p1.y, p2.x, p2.y, p.x, p.y),
+            PathSegment::Close => write!(d, "Z")
+        }.unwrap();
+    }
+    d
+}
+
+/// Collects drawing as SVG `<path>` elements in world coordinates.
+#[derive(Default)]
+pub struct Svg {
+    body: String
+}
+
+impl Svg {
+    /// Appends markup verbatim, such as grouping or animation elements.
+    pub fn raw(&mut self, markup: &str) {
+        self.body.push_str(markup);
+    }
+
+    /// Wraps the elements in a document whose viewBox is `view`, over a
+    /// `background` fill.
+    pub fn document(self, view: Rect, background: Color) -> String {
+        let mut out = String::new();
+        writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#,
+            view.left(), view.top(), view.width(), view.height()
+        ).unwrap();
+        writeln!(
+            out,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+            view.left(), view.top(), view.width(), view.height(), hex(background)
+        ).unwrap();
+        out.push_str(&self.body);
+        writeln!(out, "</svg>").unwrap();
+        out
+    }
+}
+
+impl Canvas for Svg {
+    fn stroke_path(&mut self, path: &Path, color: Color, stroke: &Stroke) {
+        let cap = match stroke.line_cap {
+            LineCap::Butt => "butt",
+            LineCap::Round => "round",
+            LineCap::Square => "square"
+        };
+        let join = match stroke.line_join {
+            LineJoin::Round => "round",
+            LineJoin::Bevel => "bevel",
+            LineJoin::Miter | LineJoin::MiterClip => "miter"
+        };
+        writeln!(
+            self.body,
+            r#"<path d="{}" fill="none" stroke="{}" stroke-opacity="{}" stroke-width="{}" stroke-linecap="{cap}" stroke-linejoin="{join}" stroke-miterlimit="{}"/>"#,
+            path_data(path), hex(color), color.alpha(), stroke.width, stroke.miter_limit
+        ).unwrap();
+    }
+
+    fn fill_path(&mut self, path: &Path, color: Color, rule: FillRule, anti_alias: bool) {
+        let rendering = if anti_alias { "auto" } else { "crispEdges" };
+        let rule = match rule {
+            FillRule::Winding => "nonzero",
+            FillRule::EvenOdd => "evenodd"
+        };
+        writeln!(
+            self.body,
+            r#"<path d="{}" fill="{}" fill-opacity="{}" fill-rule="{rule}" shape-rendering="{rendering}"/>"#,
+            path_data(path), hex(color), color.alpha()
+        ).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tiny_skia::PathBuilder;
+
+    use super::*;
+
+    #[test]
+    fn strokes_keep_their_caps_and_joins() {
+        let path = PathBuilder::from_rect(Rect::from_xywh(0., 0., 10., 10.).unwrap());
+        let mut svg = Svg::default();
+        for line_cap in [LineCap::Butt, LineCap::Square] {
+            let stroke = Stroke { width: 2., line_cap, line_join: LineJoin::Bevel, ..Stroke::default() };
+            svg.stroke_path(&path, Color::BLACK, &stroke);
+        }
+        svg.fill_path(&path, Color::WHITE, FillRule::EvenOdd, false);
+
+        let lines = svg.body.lines().collect::<Vec<_>>();
+        assert!(lines[0].contains(r#"stroke-linecap="butt" stroke-linejoin="bevel""#), "{}", lines[0]);
+        assert!(lines[1].contains(r#"stroke-linecap="square""#), "{}", lines[1]);
+        assert!(lines[2].contains(r##"fill="#ffffff" fill-opacity="1" fill-rule="evenodd" shape-rendering="crispEdges""##), "{}", lines[2]);
+    }
+}
@@ -0,0 +1,154 @@
+use std::time::Duration;
+
+const MIN_INTERVAL: Duration = Duration::from_millis(100);
+const MAX_INTERVAL: Duration = Duration::from_millis(3200);
+
+pub struct Replay {
+    ply: usize,
+    autoplay: bool,
+    generation: u64,
+    interval: Duration
+}
+
+impl Replay {
+    pub fn new(ply: usize, interval: Duration) -> Self {
+        Self {
+            ply,
+            autoplay: false,
+            generation: 0,
+            interval: interval.clamp(MIN_INTERVAL, MAX_INTERVAL)
+        }
+    }
+
+    pub fn ply(&self) -> usize {
+        self.ply
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    // Any manual navigation stops autoplay. Bumping the generation makes
+    // ticks that are already scheduled fall through.
+    fn stop(&mut self) {
+        self.autoplay = false;
+        self.generation += 1;
+    }
+
+    pub fn step_forward(&mut self, len: usize) {
+        self.stop();
+        self.ply = (self.ply + 1).min(len);
+    }
+
+    pub fn step_back(&mut self) {
+        self.stop();
+        self.ply = self.ply.saturating_sub(1);
+    }
+
+    pub fn jump_to_start(&mut self) {
+        self.stop();
+        self.ply = 0;
+    }
+
+    pub fn jump_to_end(&mut self, len: usize) {
+        self.stop();
+        self.ply = len;
+    }
+
+    /// Returns whether autoplay is now running.
+    pub fn toggle_autoplay(&mut self, len: usize) -> bool {
+        if self.autoplay {
+            self.stop();
+            return false;
+        }
+
+        self.generation += 1;
+        if self.ply >= len {
+            self.ply = 0;
+        }
+        self.autoplay = true;
+        true
+    }
+
+    /// Advances one ply for the tick of the given generation. Returns whether
+    /// another tick should be scheduled.
+    pub fn tick(&mut self, generation: u64, len: usize) -> bool {
+        if !self.autoplay || generation != self.generation {
+            return false;
+        }
+
+        self.ply = (self.ply + 1).min(len);
+        if self.ply >= len {
+            self.autoplay = false;
+        }
+        self.autoplay
+    }
+
+    pub fn faster(&mut self) {
+        self.interval = (self.interval / 2).max(MIN_INTERVAL);
+    }
+
+    pub fn slower(&mut self) {
+        self.interval = (self.interval * 2).min(MAX_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_stay_within_the_game() {
+        let mut replay = Replay::new(0, Duration::from_millis(800));
+        replay.step_back();
+        assert_eq!(replay.ply(), 0);
+        for _ in 0..5 {
+            replay.step_forward(3);
+        }
+        assert_eq!(replay.ply(), 3);
+        replay.jump_to_start();
+        assert_eq!(replay.ply(), 0);
+        replay.jump_to_end(3);
+        assert_eq!(replay.ply(), 3);
+    }
+
+    #[test]
+    fn autoplay_runs_to_the_last_ply() {
+        let mut replay = Replay::new(3, Duration::from_millis(800));
+        // From the end, autoplay starts over.
+        assert!(replay.toggle_autoplay(3));
+        assert_eq!(replay.ply(), 0);
+
+        let generation = replay.generation();
+        assert!(replay.tick(generation, 3));
+        assert!(replay.tick(generation, 3));
+        assert!(!replay.tick(generation, 3));
+        assert_eq!(replay.ply(), 3);
+        assert!(!replay.tick(generation, 3));
+        assert_eq!(replay.ply(), 3);
+    }
+
+    #[test]
+    fn stepping_cancels_scheduled_ticks() {
+        let mut replay = Replay::new(0, Duration::from_millis(800));
+        replay.toggle_autoplay(3);
+        let generation = replay.generation();
+        replay.step_forward(3);
+        assert!(!replay.tick(generation, 3));
+        assert_eq!(replay.ply(), 1);
+    }
+
+    #[test]
+    fn interval_is_clamped() {
+        let mut replay = Replay::new(0, Duration::from_secs(10));
+        assert_eq!(replay.interval(), MAX_INTERVAL);
+        for _ in 0..10 {
+            replay.faster();
+        }
+        assert_eq!(replay.interval(), MIN_INTERVAL);
+    }
+}
@@ -0,0 +1,267 @@
+use std::{error::Error, fmt::Display, mem};
+
+use tiny_skia::Color;
+
+const fn rgb(r: u8, g: u8, b: u8) -> Color {
+    unsafe { Color::from_rgba_unchecked(r as f32 / 255., g as f32 / 255., b as f32 / 255., 1.) }
+}
+
+/// How one player's marks are drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MarkStyle {
+    pub color: Color,
+    /// Stroke width in world units, or the bar thickness of a filled X.
+    pub width: f32,
+    pub filled: bool
+}
+
+impl MarkStyle {
+    const fn stroked(color: Color, width: f32) -> Self {
+        Self { color, width, filled: false }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub background: Color,
+    /// Around the laid out regions, where the window's shape doesn't match.
+    pub letterbox: Color,
+    pub grid: Color,
+    pub x: MarkStyle,
+    pub o: MarkStyle,
+    pub last_move: Color,
+    pub cursor: Color,
+    pub win: Color,
+    pub text: Color,
+    /// Width of the grid stroke, in world units (the board is 100 wide).
+    pub stroke_width: f32
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::LIGHT
+    }
+}
+
+impl Theme {
+    pub const LIGHT: Theme = Theme {
+        background: Color::WHITE,
+        letterbox: rgb(243, 236, 247),
+        grid: rgb(255, 159, 244),
+        x: MarkStyle::stroked(rgb(216, 159, 255), 5. / 3.),
+        o: MarkStyle::stroked(rgb(120, 190, 255), 5. / 3.),
+        last_move: rgb(255, 214, 102),
+        cursor: rgb(80, 80, 200),
+        win: rgb(255, 100, 120),
+        text: rgb(90, 80, 110),
+        stroke_width: 5. / 3.
+    };
+
+    pub const DARK: Theme = Theme {
+        background: rgb(30, 30, 46),
+        letterbox: rgb(17, 17, 27),
+        grid: rgb(88, 91, 112),
+        x: MarkStyle::stroked(rgb(243, 139, 168), 5. / 3.),
+        o: MarkStyle::stroked(rgb(137, 180, 250), 5. / 3.),
+        last_move: rgb(249, 226, 175),
+        cursor: rgb(166, 227, 161),
+        win: rgb(250, 179, 135),
+        text: rgb(205, 214, 244),
+        stroke_width: 5. / 3.
+    };
+
+    pub const HIGH_CONTRAST: Theme = Theme {
+        background: Color::BLACK,
+        letterbox: rgb(40, 40, 40),
+        grid: Color::WHITE,
+        x: MarkStyle::stroked(rgb(255, 255, 0), 5. / 2.),
+        o: MarkStyle::stroked(rgb(0, 255, 255), 5. / 2.),
+        last_move: rgb(255, 0, 255),
+        cursor: rgb(0, 255, 0),
+        win: Color::WHITE,
+        text: Color::WHITE,
+        stroke_width: 5. / 2.
+    };
+
+    /// Okabe–Ito vermillion and blue, distinguishable under the common forms
+    /// of colour blindness. O is filled so the marks differ in weight too.
+    pub const OKABE_ITO: Theme = Theme {
+        background: Color::WHITE,
+        letterbox: rgb(230, 230, 230),
+        grid: rgb(153, 153, 153),
+        x: MarkStyle::stroked(rgb(213, 94, 0), 2.),
+        o: MarkStyle { color: rgb(0, 114, 178), width: 2., filled: true },
+        last_move: rgb(240, 228, 66),
+        cursor: rgb(0, 158, 115),
+        win: Color::BLACK,
+        text: Color::BLACK,
+        stroke_width: 5. / 3.
+    };
+
+    /// Paul Tol's bright scheme on a dark background.
+    pub const TOL: Theme = Theme {
+        background: rgb(34, 34, 34),
+        letterbox: rgb(17, 17, 17),
+        grid: rgb(187, 187, 187),
+        x: MarkStyle::stroked(rgb(238, 102, 119), 2.),
+        o: MarkStyle::stroked(rgb(68, 119, 170), 2.),
+        last_move: rgb(204, 187, 68),
+        cursor: rgb(34, 136, 51),
+        win: rgb(102, 204, 238),
+        text: rgb(221, 221, 221),
+        stroke_width: 5. / 3.
+    };
+
+    pub const PRESETS: &[(&str, Theme)] = &[
+        ("light", Theme::LIGHT),
+        ("dark", Theme::DARK),
+        ("high-contrast", Theme::HIGH_CONTRAST),
+        ("okabe-ito", Theme::OKABE_ITO),
+        ("tol", Theme::TOL)
+    ];
+
+    pub fn preset(name: &str) -> Option<Theme> {
+        Self::PRESETS.iter().find(|&&(n, _)| n == name).map(|&(_, t)| t)
+    }
+
+    /// The preset after this one, for cycling at runtime. Custom themes
+    /// restart the cycle.
+    pub fn next_preset(&self) -> Theme {
+        let i = Self::PRESETS.iter().position(|(_, t)| t == self).map_or(0, |i| i + 1);
+        Self::PRESETS[i % Self::PRESETS.len()].1
+    }
+
+    /// Parses a theme from `key = value` lines. A `preset` key picks the
+    /// starting point, so it can only come first; every other key overrides
+    /// a single field. Blank lines and lines starting with `#` are ignored.
+    pub fn parse(src: &str) -> Result<Theme, ThemeError> {
+        let mut theme = Theme::default();
+        let mut started = false;
+
+        for (i, line) in src.lines().enumerate() {
+            let err = |kind| ThemeError { line: i + 1, kind };
+
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            }
+
+            let (key, value) = line.split_once('=').ok_or(err(ThemeErrorKind::Syntax))?;
+            let (key, value) = (key.trim(), value.trim());
+            if mem::replace(&mut started, true) && key == "preset" {
+                return Err(err(ThemeErrorKind::LatePreset))
+            }
+
+            let width = || value.parse().ok()
+                .filter(|w: &f32| w.is_finite() && *w > 0.)
+                .ok_or(err(ThemeErrorKind::InvalidValue));
+            let flag = || value.parse().ok().ok_or(err(ThemeErrorKind::InvalidValue));
+
+            let color = match key {
+                "preset" => {
+                    theme = Theme::preset(value).ok_or(err(ThemeErrorKind::UnknownPreset))?;
+                    continue
+                },
+                "stroke_width" => { theme.stroke_width = width()?; continue },
+                "x_width" => { theme.x.width = width()?; continue },
+                "o_width" => { theme.o.width = width()?; continue },
+                "x_filled" => { theme.x.filled = flag()?; continue },
+                "o_filled" => { theme.o.filled = flag()?; continue },
+                "background" => &mut theme.background,
+                "letterbox" => &mut theme.letterbox,
+                "grid" => &mut theme.grid,
+                "x" => &mut theme.x.color,
+                "o" => &mut theme.o.color,
+                "last_move" => &mut theme.last_move,
+                "cursor" => &mut theme.cursor,
+                "win" => &mut theme.win,
+                "text" => &mut theme.text,
+                _ => return Err(err(ThemeErrorKind::UnknownKey))
+            };
+            *color = parse_color(value).ok_or(err(ThemeErrorKind::InvalidValue))?;
+        }
+
+        Ok(theme)
+    }
+}
+
+/// Parses `#rrggbb` or `#rrggbbaa`.
+fn parse_color(s: &str) -> Option<Color> {
+    let hex = s.strip_prefix('#')?;
+    if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+        return None
+    }
+
+    let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    let a = if hex.len() == 8 { byte(6)? } else { 255 };
+    Some(Color::from_rgba8(byte(0)?, byte(2)?, byte(4)?, a))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThemeErrorKind {
+    Syntax,
+    UnknownKey,
+    UnknownPreset,
+    /// A `preset` after other keys, which it would undo.
+    LatePreset,
+    InvalidValue
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThemeError {
+    pub line: usize,
+    pub kind: ThemeErrorKind
+}
+
+impl Display for ThemeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let msg = match self.kind {
+            ThemeErrorKind::Syntax => "expected `key = value`",
+            ThemeErrorKind::UnknownKey => "unknown key",
+            ThemeErrorKind::UnknownPreset => "unknown preset",
+            ThemeErrorKind::LatePreset => "`preset` must come before any other key",
+            ThemeErrorKind::InvalidValue => "invalid value"
+        };
+        write!(f, "line {}: {}", self.line, msg)
+    }
+}
+
+impl Error for ThemeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_override_the_preset() {
+        let src = "# comment\n\npreset = dark\nx = #ff000080\n  o_filled = true \nstroke_width = 2.5\n";
+        let theme = Theme::parse(src).unwrap();
+        assert_eq!(theme.background, Theme::DARK.background);
+        assert_eq!(theme.x.color, Color::from_rgba8(255, 0, 0, 128));
+        assert!(theme.o.filled);
+        assert_eq!(theme.stroke_width, 2.5);
+        assert_eq!(Theme::parse(""), Ok(Theme::default()));
+    }
+
+    #[test]
+    fn bad_lines_are_reported() {
+        let cases = [
+            ("grid #fff", ThemeErrorKind::Syntax),
+            ("colour = #ffffff", ThemeErrorKind::UnknownKey),
+            ("preset = sepia", ThemeErrorKind::UnknownPreset),
+            ("grid = #fff", ThemeErrorKind::InvalidValue),
+            ("grid = #gggggg", ThemeErrorKind::InvalidValue),
+            ("x_width = -1", ThemeErrorKind::InvalidValue),
+            ("o_filled = yes", ThemeErrorKind::InvalidValue)
+        ];
+        for (line, kind) in cases {
+            assert_eq!(Theme::parse(&format!("# first\n{line}")), Err(ThemeError { line: 2, kind }), "{line}");
+        }
+    }
+
+    #[test]
+    fn preset_must_come_first() {
+        assert_eq!(Theme::parse("grid = #000000\npreset = dark"), Err(ThemeError { line: 2, kind: ThemeErrorKind::LatePreset }));
+        assert_eq!(Theme::parse("preset = dark\npreset = tol"), Err(ThemeError { line: 2, kind: ThemeErrorKind::LatePreset }));
+    }
+}
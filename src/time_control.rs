@@ -0,0 +1,181 @@
+//! Chess clocks. Each player's clock runs while it is their turn, and a
+//! player whose clock runs out loses on time.
+
+use std::{
+    error::Error,
+    fmt::Display,
+    str::FromStr,
+    time::{Duration, Instant}
+};
+
+use crate::game::Player;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeControl {
+    /// `base` for the whole game, plus `increment` after each move.
+    Total { base: Duration, increment: Duration },
+    /// Every move must be made within the limit.
+    PerMove(Duration)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseTimeControlError;
+
+impl Display for ParseTimeControlError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("invalid time control, expected e.g. `5m`, `3m+2s` or `10s/move`")
+    }
+}
+
+impl Error for ParseTimeControlError {}
+
+/// A whole number of milliseconds, seconds or minutes, e.g. `500ms`.
+fn parse_duration(s: &str) -> Result<Duration, ParseTimeControlError> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).ok_or(ParseTimeControlError)?;
+    let n = s[..split].parse().map_err(|_| ParseTimeControlError)?;
+    match &s[split..] {
+        "ms" => Ok(Duration::from_millis(n)),
+        "s" => Ok(Duration::from_secs(n)),
+        "m" => Ok(Duration::from_secs(60 * n)),
+        _ => Err(ParseTimeControlError)
+    }
+}
+
+/// `BASE`, `BASE+INCREMENT` or `LIMIT/move`.
+impl FromStr for TimeControl {
+    type Err = ParseTimeControlError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let control = if let Some(limit) = s.strip_suffix("/move") {
+            TimeControl::PerMove(parse_duration(limit)?)
+        } else {
+            let (base, increment) = s.split_once('+').unwrap_or((s, "0s"));
+            TimeControl::Total { base: parse_duration(base)?, increment: parse_duration(increment)? }
+        };
+
+        match control {
+            TimeControl::Total { base, .. } | TimeControl::PerMove(base) if base.is_zero() => Err(ParseTimeControlError),
+            _ => Ok(control)
+        }
+    }
+}
+
+/// Both players' clocks in a game.
+#[derive(Debug, Clone)]
+pub struct Clocks {
+    control: TimeControl,
+    /// What each player had left when their clock last stopped, X first.
+    remaining: [Duration; 2],
+    /// Whose clock is running and since when.
+    running: Option<(Player, Instant)>
+}
+
+impl Clocks {
+    pub fn new(control: TimeControl) -> Self {
+        let full = match control {
+            TimeControl::Total { base, .. } => base,
+            TimeControl::PerMove(limit) => limit
+        };
+        Self { control, remaining: [full; 2], running: None }
+    }
+
+    pub fn remaining(&self, player: Player, now: Instant) -> Duration {
+        let left = self.remaining[player as usize];
+        match self.running {
+            Some((p, since)) if p == player => left.saturating_sub(now.saturating_duration_since(since)),
+            _ => left
+        }
+    }
+
+    /// Both players' time left, X first.
+    pub fn both(&self, now: Instant) -> [Duration; 2] {
+        [self.remaining(Player::X, now), self.remaining(Player::O, now)]
+    }
+
+    /// Starts `player`'s clock for their turn.
+    pub fn start(&mut self, player: Player, now: Instant) {
+        if let TimeControl::PerMove(limit) = self.control {
+            self.remaining[player as usize] = limit;
+        }
+        self.running = Some((player, now));
+    }
+
+    /// Stops the running clock at the end of a move, crediting any
+    /// increment.
+    pub fn stop(&mut self, now: Instant) {
+        let Some((player, _)) = self.running else { return };
+        let left = self.remaining(player, now);
+        self.remaining[player as usize] = match self.control {
+            TimeControl::Total { increment, .. } if !left.is_zero() => left + increment,
+            _ => left
+        };
+        self.running = None;
+    }
+
+    /// When the running clock runs out.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.running.map(|(p, since)| since + self.remaining[p as usize])
+    }
+
+    /// The player whose clock has run out by `now`, if any.
+    pub fn flagged(&self, now: Instant) -> Option<Player> {
+        self.running.map(|(p, _)| p).filter(|&p| self.remaining(p, now).is_zero())
+    }
+
+    /// How long `player` can afford to think about this move, with
+    /// `moves_left` of theirs still to come at most, this one included.
+    pub fn budget(&self, player: Player, now: Instant, moves_left: u32) -> Duration {
+        let left = self.remaining(player, now);
+        match self.control {
+            // Spread what's left over the rest of the game, but always keep
+            // half in hand in case the search overruns.
+            TimeControl::Total { increment, .. } => (left / moves_left.max(1) + increment).min(left / 2),
+            TimeControl::PerMove(_) => left / 2
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEC: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn parses_each_kind() {
+        assert_eq!("5m".parse(), Ok(TimeControl::Total { base: 300 * SEC, increment: Duration::ZERO }));
+        assert_eq!("3m+2s".parse(), Ok(TimeControl::Total { base: 180 * SEC, increment: 2 * SEC }));
+        assert_eq!("500ms/move".parse(), Ok(TimeControl::PerMove(SEC / 2)));
+        for bad in ["", "5", "5h", "0s", "m+2s", "10s/turn"] {
+            assert_eq!(bad.parse::<TimeControl>(), Err(ParseTimeControlError), "{bad}");
+        }
+    }
+
+    #[test]
+    fn only_the_running_clock_counts_down() {
+        let t0 = Instant::now();
+        let mut clocks = Clocks::new(TimeControl::Total { base: 10 * SEC, increment: 2 * SEC });
+        clocks.start(Player::X, t0);
+        assert_eq!(clocks.both(t0 + 3 * SEC), [7 * SEC, 10 * SEC]);
+
+        clocks.stop(t0 + 3 * SEC);
+        clocks.start(Player::O, t0 + 3 * SEC);
+        assert_eq!(clocks.both(t0 + 4 * SEC), [9 * SEC, 9 * SEC]);
+        assert_eq!(clocks.deadline(), Some(t0 + 13 * SEC));
+    }
+
+    #[test]
+    fn per_move_limit_resets_each_turn() {
+        let t0 = Instant::now();
+        let mut clocks = Clocks::new(TimeControl::PerMove(5 * SEC));
+        clocks.start(Player::X, t0);
+        clocks.stop(t0 + 4 * SEC);
+        clocks.start(Player::O, t0 + 4 * SEC);
+        clocks.stop(t0 + 5 * SEC);
+        clocks.start(Player::X, t0 + 5 * SEC);
+        assert_eq!(clocks.remaining(Player::X, t0 + 5 * SEC), 5 * SEC);
+
+        assert_eq!(clocks.flagged(t0 + 9 * SEC), None);
+        assert_eq!(clocks.flagged(t0 + 10 * SEC), Some(Player::X));
+    }
+}
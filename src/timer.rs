@@ -1,37 +1,158 @@
-use std::{pin::Pin, rc::{Rc, Weak}, sync::atomic::{AtomicBool, Ordering}, task::{Context, Poll}, time::{Duration, Instant}};
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    pin::Pin,
+    rc::{Rc, Weak},
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant}
+};
 
-use atomic_waker::AtomicWaker;
+/// Where timers get the time from.
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when told to, for tests.
+#[cfg(test)]
+#[derive(Clone)]
+pub struct VirtualClock {
+    now: Rc<std::cell::Cell<Instant>>
+}
+
+#[cfg(test)]
+impl VirtualClock {
+    pub fn new() -> Self {
+        Self { now: Rc::new(std::cell::Cell::new(Instant::now())) }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+}
+
+#[cfg(test)]
+impl Clock for VirtualClock {
+    fn now(&self) -> Instant {
+        self.now.get()
+    }
+}
 
 #[derive(Default)]
-struct TimerInner {
-    waker: AtomicWaker,
-    flag: AtomicBool
+struct TimerState {
+    fired: bool,
+    waker: Option<Waker>
 }
 
-pub struct Timer {
-    inner: Option<Rc<TimerInner>>
+/// Pending deadlines, keyed by when they are due and then by creation order
+/// so that timers due together fire first come, first served.
+#[derive(Default)]
+struct Queue {
+    pending: BTreeMap<(Instant, u64), Rc<RefCell<TimerState>>>,
+    seq: u64
 }
 
-impl Timer {
-    pub fn at(instant: Instant) -> (PendingTimer, Timer) {
-        let inner = Rc::<TimerInner>::default();
-        (
-            PendingTimer {
-                at: instant,
-                inner: Rc::downgrade(&inner)
-            },
-            Timer { inner: Some(inner) }
-        )
+impl Queue {
+    fn insert(&mut self, at: Instant, state: Rc<RefCell<TimerState>>) -> (Instant, u64) {
+        let key = (at, self.seq);
+        self.seq += 1;
+        self.pending.insert(key, state);
+        key
     }
+}
 
-    pub fn after(duration: Duration) -> (PendingTimer, Timer) {
-        let instant = Instant::now() + duration;
-        Self::at(instant)
+/// The deadlines of every live [`Timer`]. Whoever owns the event loop sleeps
+/// until [`Self::next_deadline`] and then calls [`Self::fire`].
+#[derive(Clone)]
+pub struct Timers {
+    clock: Rc<dyn Clock>,
+    queue: Rc<RefCell<Queue>>
+}
+
+impl Default for Timers {
+    fn default() -> Self {
+        Self::new(SystemClock)
+    }
+}
+
+impl Timers {
+    pub fn new(clock: impl Clock + 'static) -> Self {
+        Self { clock: Rc::new(clock), queue: Rc::default() }
+    }
+
+    pub fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    pub fn at(&self, deadline: Instant) -> Timer {
+        let state = Rc::<RefCell<TimerState>>::default();
+        let key = self.queue.borrow_mut().insert(deadline, state.clone());
+        Timer { clock: self.clock.clone(), queue: Rc::downgrade(&self.queue), key, state }
     }
 
-    #[expect(dead_code)]
-    pub fn never() -> Timer {
-        Timer { inner: None }
+    pub fn after(&self, duration: Duration) -> Timer {
+        self.at(self.now() + duration)
+    }
+
+    /// Ticks every `period`, starting one period from now.
+    pub fn interval(&self, period: Duration) -> Interval {
+        Interval { timer: self.after(period), period }
+    }
+
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.queue.borrow().pending.first_key_value().map(|(&(at, _), _)| at)
+    }
+
+    /// Fires every timer due by `now`, in the order they are due.
+    pub fn fire(&self, now: Instant) {
+        loop {
+            let state = {
+                let mut queue = self.queue.borrow_mut();
+                match queue.pending.first_entry() {
+                    Some(entry) if entry.key().0 <= now => entry.remove(),
+                    _ => break
+                }
+            };
+
+            let waker = {
+                let mut state = state.borrow_mut();
+                state.fired = true;
+                state.waker.take()
+            };
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// Completes once its deadline passes. Dropping it withdraws the deadline.
+pub struct Timer {
+    clock: Rc<dyn Clock>,
+    queue: Weak<RefCell<Queue>>,
+    key: (Instant, u64),
+    state: Rc<RefCell<TimerState>>
+}
+
+impl Timer {
+    pub fn deadline(&self) -> Instant {
+        self.key.0
+    }
+
+    /// Moves the deadline, re-arming the timer if it already fired.
+    pub fn reset(&mut self, deadline: Instant) {
+        let Some(queue) = self.queue.upgrade() else { return };
+        let mut queue = queue.borrow_mut();
+        queue.pending.remove(&self.key);
+        self.state.borrow_mut().fired = false;
+        self.key = queue.insert(deadline, self.state.clone());
     }
 }
 
@@ -39,53 +160,132 @@ impl Future for Timer {
     type Output = ();
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let Some(ref inner) = self.inner else { return Poll::Pending };
-
-        if inner.flag.load(Ordering::Relaxed) {
+        let mut state = self.state.borrow_mut();
+        if state.fired {
             return Poll::Ready(())
         }
 
-        inner.waker.register(cx.waker());
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
 
-        if inner.flag.load(Ordering::Relaxed) {
-            Poll::Ready(())
-        } else {
-            Poll::Pending
+impl Drop for Timer {
+    fn drop(&mut self) {
+        if let Some(queue) = self.queue.upgrade() {
+            queue.borrow_mut().pending.remove(&self.key);
         }
     }
 }
 
-pub struct PendingTimer {
-    pub at: Instant,
-    inner: Weak<TimerInner>
+/// A timer that re-arms itself every `period`.
+pub struct Interval {
+    timer: Timer,
+    period: Duration
 }
 
-impl PendingTimer {
-    pub fn set(self) {
-        if let Some(inner) = self.inner.upgrade() {
-            inner.flag.store(true, Ordering::Relaxed);
-            inner.waker.wake();
-        }
+impl Interval {
+    /// Takes effect from the next tick on.
+    pub fn set_period(&mut self, period: Duration) {
+        self.period = period;
     }
-}
 
-impl PartialEq for PendingTimer {
-    fn eq(&self, other: &Self) -> bool {
-        self.at.eq(&other.at)
+    /// Waits for the next tick and returns when it was due. A tick that
+    /// comes more than a period late pushes the later ones back instead of
+    /// letting them bunch up.
+    pub async fn tick(&mut self) -> Instant {
+        (&mut self.timer).await;
+
+        let due = self.timer.deadline();
+        let now = self.timer.clock.now();
+        let next = due + self.period;
+        self.timer.reset(if next < now { now + self.period } else { next });
+        due
     }
 }
 
-impl Eq for PendingTimer {}
+#[cfg(test)]
+mod tests {
+    use std::{
+        pin::pin,
+        sync::{Arc, Mutex},
+        task::Wake
+    };
 
-impl PartialOrd for PendingTimer {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
+    use super::*;
+
+    struct Record(usize, Arc<Mutex<Vec<usize>>>);
+
+    impl Wake for Record {
+        fn wake(self: Arc<Self>) {
+            self.1.lock().unwrap().push(self.0);
+        }
     }
-}
 
-impl Ord for PendingTimer {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        // Order is correct. This is meant to be inserted into a max-heap (BinaryHeap)
-        other.at.cmp(&self.at)
+    fn poll(fut: Pin<&mut impl Future>) -> bool {
+        fut.poll(&mut Context::from_waker(Waker::noop())).is_ready()
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn dropping_withdraws_the_deadline() {
+        let timers = Timers::default();
+        let timer = timers.after(Duration::from_secs(1));
+        assert!(timers.next_deadline().is_some());
+        drop(timer);
+        assert_eq!(timers.next_deadline(), None);
+    }
+
+    #[test]
+    fn equal_deadlines_fire_in_creation_order() {
+        let timers = Timers::default();
+        let at = Instant::now();
+        let woken = Arc::new(Mutex::new(Vec::new()));
+
+        let mut pending: Vec<_> = (0..4).map(|_| timers.at(at)).collect();
+        // Register in reverse so only creation order can explain the result.
+        for (i, timer) in pending.iter_mut().enumerate().rev() {
+            let waker = Waker::from(Arc::new(Record(i, woken.clone())));
+            assert!(Pin::new(timer).poll(&mut Context::from_waker(&waker)).is_pending());
+        }
+
+        timers.fire(at);
+        assert_eq!(*woken.lock().unwrap(), [0, 1, 2, 3]);
+        assert!(pending.iter_mut().all(|t| poll(Pin::new(t))));
+    }
+
+    #[test]
+    fn reset_moves_the_deadline_and_rearms() {
+        let timers = Timers::default();
+        let at = Instant::now();
+        let mut timer = timers.at(at);
+
+        timers.fire(at);
+        assert!(poll(Pin::new(&mut timer)));
+
+        timer.reset(at + Duration::from_secs(1));
+        assert!(!poll(Pin::new(&mut timer)));
+        assert_eq!(timers.next_deadline(), Some(at + Duration::from_secs(1)));
+
+        timer.reset(at + Duration::from_secs(2));
+        assert_eq!(timers.next_deadline(), Some(at + Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn interval_rearms_after_each_tick() {
+        let timers = Timers::default();
+        let period = Duration::from_secs(3600);
+        let mut interval = timers.interval(period);
+        let first = timers.next_deadline().unwrap();
+
+        {
+            let mut tick = pin!(interval.tick());
+            assert!(!poll(tick.as_mut()));
+            timers.fire(first);
+            assert!(poll(tick.as_mut()));
+        }
+        assert_eq!(timers.next_deadline(), Some(first + period));
+
+        drop(interval);
+        assert_eq!(timers.next_deadline(), None);
+    }
+}
@@ -0,0 +1,30 @@
+use std::time::Duration;
+
+use ttt::{
+    engine::{External, Go},
+    game::Record
+};
+
+fn engine() -> External {
+    External::spawn(env!("CARGO_BIN_EXE_ttt"), &["engine".to_owned()]).unwrap()
+}
+
+#[test]
+fn plays_through_the_engine_protocol() {
+    let mut engine = engine();
+    assert_eq!(engine.name(), "ttt");
+    engine.new_game().unwrap();
+
+    let record = "a1 b1 c1 d1 a2 b2 c2 d2 b3 a3".parse::<Record>().unwrap();
+    let (best, line) = engine.go(&record, Go { movetime: Some(Duration::from_millis(100)), ..Go::default() }).unwrap();
+    let (x, y) = best.unwrap();
+    assert!(record.state_at(record.len()).do_move(x, y).is_ok());
+    assert_eq!(line.unwrap().pv[0], (x, y));
+}
+
+#[test]
+fn no_move_once_the_game_is_over() {
+    let mut engine = engine();
+    let record = "a1 a2 b1 b2 c1 c2 d1".parse::<Record>().unwrap();
+    assert_eq!(engine.go(&record, Go { depth: Some(1), ..Go::default() }).unwrap(), (None, None));
+}